@@ -5,8 +5,11 @@ use crate::{
         CBLCollection_Name, CBLCollection_Count, CBLCollection_FullName, CBLCollection_Database,
     },
     scope::Scope,
-    Database,
+    Database, Document, Result,
+    paginator::{Cursor, Paginator},
+    query_builder::{DataSource, Expression, Meta, OrderBy, QueryBuilder, SelectResult},
 };
+use std::collections::VecDeque;
 
 pub static DEFAULT_NAME: &str = "_default";
 
@@ -103,6 +106,50 @@ impl Collection {
         unsafe { CBLCollection_Count(self.get_ref()) }
     }
 
+    /// Iterates over the IDs of the documents in the collection, in ID order.
+    pub fn document_ids(&self) -> Result<DocumentIds> {
+        self.document_ids_with(&DocumentsOptions::default())
+    }
+
+    /// Iterates over the IDs of the documents of the collection selected by the options.
+    /// The IDs are queried in batches, so the memory used doesn't depend on the number of
+    /// documents, and documents changed during the iteration are seen at most once.
+    pub fn document_ids_with(&self, options: &DocumentsOptions) -> Result<DocumentIds> {
+        let mut query = QueryBuilder::select([SelectResult::expression(Meta::id())])
+            .from(DataSource::collection(self));
+        if let Some(filter) = &options.filter {
+            query = query.where_(filter.clone());
+        }
+        let key = options
+            .order_by
+            .clone()
+            .unwrap_or_else(|| OrderBy::asc(Meta::id()));
+        Ok(DocumentIds {
+            paginator: Paginator::new(&self.database(), query, key, options.batch_size)?,
+            cursor: None,
+            ids: VecDeque::new(),
+            done: false,
+        })
+    }
+
+    /// Iterates over the documents in the collection, in ID order.
+    pub fn documents(&self) -> Result<Documents> {
+        self.documents_with(&DocumentsOptions::default())
+    }
+
+    /// Iterates over the documents of the collection selected by the options, read in
+    /// batches like `document_ids_with`. Documents deleted during the iteration are skipped.
+    /// If reading a batch of IDs fails, the documents of the IDs read before are returned
+    /// first, then the error, which ends the iteration.
+    pub fn documents_with(&self, options: &DocumentsOptions) -> Result<Documents> {
+        Ok(Documents {
+            collection: self.clone(),
+            ids: self.document_ids_with(options)?,
+            batch_size: options.batch_size.max(1) as usize,
+            documents: VecDeque::new(),
+        })
+    }
+
     /// Registers a collection change listener callback. It will be called after one or more documents are changed on disk.
     pub fn add_listener(
         &mut self,
@@ -145,6 +192,116 @@ impl Clone for Collection {
     }
 }
 
+/// Options of `Collection::documents_with` and `Collection::document_ids_with`.
+#[derive(Debug, Clone)]
+pub struct DocumentsOptions {
+    /// The order of the documents, by document ID if None. The document ID breaks ties, and
+    /// documents whose sort key is null or missing come first, or last in descending order,
+    /// see `Paginator`.
+    pub order_by: Option<OrderBy>,
+    /// The condition the documents must meet. It is a query expression, part of the `WHERE`
+    /// clause of the queries reading the IDs, so it is evaluated by the database and can use
+    /// its indexes.
+    pub filter: Option<Expression>,
+    /// The number of documents read at a time.
+    pub batch_size: u32,
+}
+
+impl Default for DocumentsOptions {
+    fn default() -> Self {
+        Self {
+            order_by: None,
+            filter: None,
+            batch_size: 100,
+        }
+    }
+}
+
+/// An iterator over document IDs, returned by `Collection::document_ids`.
+pub struct DocumentIds {
+    paginator: Paginator,
+    cursor: Option<Cursor>,
+    ids: VecDeque<String>,
+    done: bool,
+}
+
+impl DocumentIds {
+    // Reads the next batch, returning false at the end.
+    fn fetch(&mut self) -> Result<bool> {
+        if self.done {
+            return Ok(false);
+        }
+        let page = self.paginator.page(self.cursor.as_ref())?;
+        self.ids.extend(
+            page.rows
+                .iter()
+                .filter_map(|row| row.get(0).as_string().map(str::to_string)),
+        );
+        self.done = page.next.is_none();
+        self.cursor = page.next;
+        Ok(!self.ids.is_empty())
+    }
+}
+
+impl Iterator for DocumentIds {
+    type Item = Result<String>;
+
+    fn next(&mut self) -> Option<Result<String>> {
+        if self.ids.is_empty() {
+            match self.fetch() {
+                Ok(true) => {}
+                Ok(false) => return None,
+                Err(error) => {
+                    self.done = true;
+                    return Some(Err(error));
+                }
+            }
+        }
+        self.ids.pop_front().map(Ok)
+    }
+}
+
+/// An iterator over documents, returned by `Collection::documents`.
+pub struct Documents {
+    collection: Collection,
+    ids: DocumentIds,
+    batch_size: usize,
+    documents: VecDeque<Result<Document>>,
+}
+
+impl Iterator for Documents {
+    type Item = Result<Document>;
+
+    fn next(&mut self) -> Option<Result<Document>> {
+        while self.documents.is_empty() {
+            let mut ids = Vec::new();
+            let mut error = None;
+            for id in self.ids.by_ref().take(self.batch_size) {
+                match id {
+                    Ok(id) => ids.push(id),
+                    Err(e) => {
+                        error = Some(e);
+                        break;
+                    }
+                }
+            }
+            if ids.is_empty() && error.is_none() {
+                return None;
+            }
+            let ids: Vec<&str> = ids.iter().map(String::as_str).collect();
+            self.documents.extend(
+                self.collection
+                    .get_documents(&ids)
+                    .into_iter()
+                    .filter_map(Result::transpose)
+                    .filter(|document| !document.as_ref().is_ok_and(Document::is_deleted)),
+            );
+            self.documents.extend(error.map(Err));
+        }
+        self.documents.pop_front()
+    }
+}
+
 /// A collection change listener callback, invoked after one or more documents are changed on disk.
 pub type CollectionChangeListener = Box<dyn Fn(Collection, Vec<String>)>;
 
@@ -34,6 +34,7 @@ use crate::{
     },
     Listener, check_error, Error, CouchbaseLiteError,
     collection::Collection,
+    index, query_cache,
     scope::Scope,
     MutableArray,
 };
@@ -495,18 +496,20 @@ impl Database {
     /// Delete an existing collection.
     /// The default collection cannot be deleted.
     pub fn delete_collection(&self, collection_name: String, scope_name: String) -> Result<()> {
-        let collection_name = from_str(&collection_name);
-        let scope_name = from_str(&scope_name);
+        let collection_name_s = from_str(&collection_name);
+        let scope_name_s = from_str(&scope_name);
         unsafe {
             check_bool(|error| {
                 CBLDatabase_DeleteCollection(
                     self.get_ref(),
-                    collection_name.get_ref(),
-                    scope_name.get_ref(),
+                    collection_name_s.get_ref(),
+                    scope_name_s.get_ref(),
                     error,
                 )
             })
-        }
+        }?;
+        query_cache::schema_changed(self);
+        index::forget_collection(self, &format!("{scope_name}.{collection_name}"))
     }
 
     /// Returns the default scope.
@@ -27,7 +27,7 @@ use crate::{
         CBLDocument_RevisionID, CBLDocument_Sequence, CBLDocument_SetJSON,
         CBLDocument_SetProperties, CBLError, FLString, kCBLConcurrencyControlFailOnConflict,
         kCBLConcurrencyControlLastWriteWins, CBLDocumentChange, CBLCollection,
-        CBLCollection_GetMutableDocument, CBLCollection_SaveDocument,
+        CBLCollection_GetDocument, CBLCollection_GetMutableDocument, CBLCollection_SaveDocument,
         CBLCollection_SaveDocumentWithConcurrencyControl,
         CBLCollection_SaveDocumentWithConflictHandler, CBLCollection_DeleteDocument,
         CBLCollection_DeleteDocumentWithConcurrencyControl, CBLCollection_PurgeDocument,
@@ -35,10 +35,12 @@ use crate::{
         CBLCollection_SetDocumentExpiration, CBLCollection_AddDocumentChangeListener,
     },
     slice::from_str,
-    CblRef, CouchbaseLiteError, Database, Dict, Error, ListenerToken, MutableDict, Result,
-    Timestamp, check_bool, check_failure, failure, release, retain, Listener,
+    CblRef, CouchbaseLiteError, Database, Dict, Error, ErrorCode, ListenerToken, MutableDict,
+    Result, Timestamp, Value, check_bool, check_failure, failure, release, retain, Listener,
     collection::Collection,
 };
+use std::borrow::BorrowMut;
+use std::fmt;
 
 /// An in-memory copy of a document.
 #[derive(Debug)]
@@ -53,7 +55,28 @@ impl CblRef for Document {
     }
 }
 
+/// The properties of a document read by `Collection::get_documents_properties`. They aren't
+/// copied: the read-only document they belong to is kept until they are dropped.
+#[derive(Debug)]
+pub struct DocumentProperties {
+    document: Document,
+}
+
+impl DocumentProperties {
+    /// Returns the properties, which are valid as long as `self`. The properties of a deleted
+    /// document are empty.
+    pub fn as_dict(&self) -> Dict {
+        self.document.properties()
+    }
+
+    /// Returns the value of a property.
+    pub fn get(&self, key: &str) -> Value {
+        self.as_dict().get(key)
+    }
+}
+
 /// Conflict-handling options when saving or deleting a document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum ConcurrencyControl {
     /// The current save/delete will overwrite a conflicting revision if there is a conflict.
     LastWriteWins = kCBLConcurrencyControlLastWriteWins as isize,
@@ -61,6 +84,114 @@ pub enum ConcurrencyControl {
     FailOnConflict = kCBLConcurrencyControlFailOnConflict as isize,
 }
 
+/// The default number of documents saved per transaction by `Collection::save_documents`.
+pub const DEFAULT_TRANSACTION_SIZE: usize = 1000;
+
+/// The outcome of saving one document with `Collection::save_documents`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveOutcome {
+    Saved,
+    /// The document was changed since it was loaded, and the concurrency control is
+    /// `FailOnConflict`.
+    Conflict,
+    Failed(Error),
+}
+
+/// The outcomes of `Collection::save_documents`, in the order of the documents.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SaveReport {
+    pub outcomes: Vec<(String, SaveOutcome)>,
+}
+
+impl SaveReport {
+    /// Returns the number of documents saved.
+    pub fn saved(&self) -> usize {
+        self.outcomes
+            .iter()
+            .filter(|(_, outcome)| *outcome == SaveOutcome::Saved)
+            .count()
+    }
+
+    /// Returns the IDs of the documents not saved because of a conflict.
+    pub fn conflicts(&self) -> Vec<&str> {
+        self.outcomes
+            .iter()
+            .filter(|(_, outcome)| *outcome == SaveOutcome::Conflict)
+            .map(|(id, _)| id.as_str())
+            .collect()
+    }
+
+    /// Returns the IDs of the documents not saved because of another error, with the error.
+    pub fn failures(&self) -> Vec<(&str, Error)> {
+        self.outcomes
+            .iter()
+            .filter_map(|(id, outcome)| match outcome {
+                SaveOutcome::Failed(error) => Some((id.as_str(), *error)),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Returns true if all the documents were saved.
+    pub fn is_success(&self) -> bool {
+        self.saved() == self.outcomes.len()
+    }
+}
+
+/// The error of `Collection::save_documents` when a transaction can't be started or committed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveDocumentsError {
+    pub error: Error,
+    /// The outcomes of the documents up to the failed transaction. The documents of a
+    /// transaction that couldn't be committed were rolled back: the ones saved in it are
+    /// reported as `Failed` with `error`. The following documents aren't reported.
+    pub report: SaveReport,
+}
+
+impl fmt::Display for SaveDocumentsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "saving documents failed after {} of them: {}",
+            self.report.saved(),
+            self.error
+        )
+    }
+}
+
+impl std::error::Error for SaveDocumentsError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// What the closure of `Collection::update` decides to do with the updated document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Update {
+    Save,
+    /// Nothing is saved.
+    Abort,
+}
+
+/// Options of `Collection::update_with`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpdateOptions {
+    /// The maximum number of times the document is loaded, updated and saved, when saving
+    /// fails because of a conflict. It must be at least 1.
+    pub max_attempts: u32,
+    /// Whether a missing or deleted document is created, with the properties set by the update.
+    pub create_missing: bool,
+}
+
+impl Default for UpdateOptions {
+    fn default() -> Self {
+        Self {
+            max_attempts: 5,
+            create_missing: true,
+        }
+    }
+}
+
 /// Custom conflict handler for use when saving or deleting a document. This handler is called
 /// if the save would cause a conflict, i.e. if the document in the database has been updated
 /// (probably by a pull replicator, or by application code on another thread)
@@ -332,6 +463,16 @@ unsafe extern "C" fn c_collection_document_change_listener(
     }
 }
 
+pub(crate) fn not_found_as_none<T>(result: Result<T>) -> Result<Option<T>> {
+    match result {
+        Ok(value) => Ok(Some(value)),
+        Err(error) if error.code == ErrorCode::CouchbaseLite(CouchbaseLiteError::NotFound) => {
+            Ok(None)
+        }
+        Err(error) => Err(error),
+    }
+}
+
 impl Collection {
     /// Reads a document from the collection, returning a new Document object.
     /// Each call to this function creates a new object (which must later be released.).
@@ -356,6 +497,48 @@ impl Collection {
         }
     }
 
+    /// Reads documents from the collection, returning for each ID, in the same order,
+    /// the document or None if it doesn't exist. Like `get_document`, deleted documents are
+    /// returned, see `Document::is_deleted`.
+    pub fn get_documents(&self, ids: &[&str]) -> Vec<Result<Option<Document>>> {
+        ids.iter()
+            .map(|id| not_found_as_none(self.get_document(id)))
+            .collect()
+    }
+
+    /// Reads the properties of documents from the collection, returning for each ID, in the
+    /// same order, the properties or None if the document doesn't exist.
+    /// The documents are read in read-only form, which is cheaper than the mutable documents of
+    /// `get_documents` when they aren't modified.
+    pub fn get_documents_properties(
+        &self,
+        ids: &[&str],
+    ) -> Vec<Result<Option<DocumentProperties>>> {
+        ids.iter()
+            .map(|id| {
+                not_found_as_none(unsafe {
+                    let mut error = CBLError::default();
+                    let doc = CBLCollection_GetDocument(
+                        self.get_ref(),
+                        from_str(id).get_ref(),
+                        &mut error,
+                    );
+                    if doc.is_null() {
+                        if error.code == 0 {
+                            Err(Error::cbl_error(CouchbaseLiteError::NotFound))
+                        } else {
+                            failure(error)
+                        }
+                    } else {
+                        Ok(DocumentProperties {
+                            document: Document::take_ownership(doc.cast_mut()),
+                        })
+                    }
+                })
+            })
+            .collect()
+    }
+
     /// Saves a document to the collection.
     pub fn save_document(&mut self, doc: &mut Document) -> Result<()> {
         unsafe {
@@ -386,6 +569,57 @@ impl Collection {
         }
     }
 
+    /// Loads a document, updates its properties and saves it, see `update_with`.
+    pub fn update<F>(&mut self, id: &str, update: F) -> Result<Option<Document>>
+    where
+        F: FnMut(&mut MutableDict) -> Result<Update>,
+    {
+        self.update_with(id, &UpdateOptions::default(), update)
+    }
+
+    /// Loads a document, updates its properties and saves it with
+    /// `ConcurrencyControl::FailOnConflict`. If the document was changed in the meantime,
+    /// it is loaded and updated again, up to `max_attempts` times, after which the
+    /// `Conflict` error is returned.
+    /// Returns the saved document, or None if the closure returned `Update::Abort`. Nothing is
+    /// saved either if the closure returns an error, which is returned.
+    /// A missing document is `NotFound`, unless `create_missing` is set. A `max_attempts` of 0
+    /// is `InvalidParameter`.
+    pub fn update_with<F>(
+        &mut self,
+        id: &str,
+        options: &UpdateOptions,
+        mut update: F,
+    ) -> Result<Option<Document>>
+    where
+        F: FnMut(&mut MutableDict) -> Result<Update>,
+    {
+        if options.max_attempts == 0 {
+            return Err(Error::cbl_error(CouchbaseLiteError::InvalidParameter));
+        }
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let mut doc = match not_found_as_none(self.get_document(id))? {
+                Some(doc) if !doc.is_deleted() => doc,
+                _ if options.create_missing => Document::new_with_id(id),
+                _ => return Err(Error::cbl_error(CouchbaseLiteError::NotFound)),
+            };
+            if update(&mut doc.mutable_properties())? == Update::Abort {
+                return Ok(None);
+            }
+            match self
+                .save_document_with_concurency_control(&mut doc, ConcurrencyControl::FailOnConflict)
+            {
+                Ok(()) => return Ok(Some(doc)),
+                Err(error)
+                    if error.code == ErrorCode::CouchbaseLite(CouchbaseLiteError::Conflict)
+                        && attempts < options.max_attempts => {}
+                Err(error) => return Err(error),
+            }
+        }
+    }
+
     /// Saves a document to the collection, allowing for custom conflict handling in the event
     /// that the document has been updated since \p doc was loaded.
     pub fn save_document_resolving(
@@ -410,6 +644,69 @@ impl Collection {
         }
     }
 
+    /// Saves documents to the collection, in transactions of `DEFAULT_TRANSACTION_SIZE`
+    /// documents, see `save_documents_with_transaction_size`.
+    pub fn save_documents<I>(
+        &mut self,
+        docs: I,
+        concurrency: ConcurrencyControl,
+    ) -> std::result::Result<SaveReport, SaveDocumentsError>
+    where
+        I: IntoIterator,
+        I::Item: BorrowMut<Document>,
+    {
+        self.save_documents_with_transaction_size(docs, concurrency, DEFAULT_TRANSACTION_SIZE)
+    }
+
+    /// Saves documents to the collection, in transactions of `transaction_size` documents.
+    /// A document that can't be saved, because of a conflict or another error, is reported
+    /// and the others are still saved.
+    /// An error is only returned if a transaction can't be started or committed, with the
+    /// report of the documents up to it; the documents of the previous transactions are saved.
+    pub fn save_documents_with_transaction_size<I>(
+        &mut self,
+        docs: I,
+        concurrency: ConcurrencyControl,
+        transaction_size: usize,
+    ) -> std::result::Result<SaveReport, SaveDocumentsError>
+    where
+        I: IntoIterator,
+        I::Item: BorrowMut<Document>,
+    {
+        let mut db = self.database();
+        let mut docs = docs.into_iter().peekable();
+        let mut report = SaveReport::default();
+        while docs.peek().is_some() {
+            let transaction = match db.begin_transaction() {
+                Ok(transaction) => transaction,
+                Err(error) => return Err(SaveDocumentsError { error, report }),
+            };
+            let first = report.outcomes.len();
+            for mut doc in docs.by_ref().take(transaction_size.max(1)) {
+                let doc = doc.borrow_mut();
+                let outcome = match self.save_document_with_concurency_control(doc, concurrency) {
+                    Ok(()) => SaveOutcome::Saved,
+                    Err(error)
+                        if error.code == ErrorCode::CouchbaseLite(CouchbaseLiteError::Conflict) =>
+                    {
+                        SaveOutcome::Conflict
+                    }
+                    Err(error) => SaveOutcome::Failed(error),
+                };
+                report.outcomes.push((doc.id().to_string(), outcome));
+            }
+            if let Err(error) = transaction.commit() {
+                for (_, outcome) in &mut report.outcomes[first..] {
+                    if *outcome == SaveOutcome::Saved {
+                        *outcome = SaveOutcome::Failed(error);
+                    }
+                }
+                return Err(SaveDocumentsError { error, report });
+            }
+        }
+        Ok(report)
+    }
+
     /// Deletes a document from the collection. Deletions are replicated.
     pub fn delete_document(&mut self, doc: &Document) -> Result<()> {
         unsafe {
@@ -16,9 +16,9 @@
 //
 
 use crate::{
-    Blob, CblRef, MutableArray, MutableDict, Timestamp,
+    Blob, CblRef, MutableArray, MutableDict, TIMESTAMP_NONE, Timestamp,
     slice::{NULL_SLICE, from_bytes, from_str},
-    error::{Error, Result},
+    error::{CouchbaseLiteError, Error, Result},
     c_api::{
         FLArray, FLArrayIterator, FLArrayIterator_Begin, FLArrayIterator_GetCount,
         FLArrayIterator_GetValue, FLArrayIterator_GetValueAt, FLArrayIterator_Next, FLArray_Count,
@@ -30,8 +30,10 @@ use crate::{
         FLError_kFLInvalidData, FLSlice_Copy, FLValue, FLValue_AsArray, FLValue_AsBool,
         FLValue_AsData, FLValue_AsDict, FLValue_AsDouble, FLValue_AsFloat, FLValue_AsInt,
         FLValue_AsString, FLValue_AsTimestamp, FLValue_AsUnsigned, FLValue_GetType,
-        FLValue_IsEqual, FLValue_IsInteger, FLValue_IsUnsigned, FLValue_IsDouble,
-        FLValue_IsMutable, FLValue_ToJSON, _FLValue, FLValue_FindDoc, FLDictIterator_End,
+        FLValue_IsInteger, FLValue_IsUnsigned, FLValue_IsDouble, FLValue_IsMutable, FLValue_ToJSON,
+        _FLValue, FLValue_FindDoc, FLDictIterator_End, FLData_Dump, FLEncoder_Finish,
+        FLEncoder_Free, FLEncoder_New, FLEncoder_WriteValue, FLSlice, FLSliceResult,
+        FLValue_FromData, FLJSON5_ToJSON, FLValue_ToJSONX,
     },
 };
 #[cfg(feature = "enterprise")]
@@ -41,8 +43,11 @@ use crate::{
 };
 
 use enum_primitive::FromPrimitive;
+use std::cmp::Ordering;
 use std::collections::HashSet;
 use std::fmt;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::marker::PhantomData;
 use std::mem::MaybeUninit;
 use std::ptr;
 use std::str;
@@ -90,6 +95,41 @@ impl Fleece {
         }
     }
 
+    /// Parses JSON5, a superset of JSON allowing comments, unquoted keys, single-quoted strings
+    /// and trailing commas.
+    pub fn parse_json5(json5: &str) -> Result<Self> {
+        unsafe {
+            let mut error: FLError = 0;
+            let json = FLJSON5_ToJSON(
+                from_str(json5).get_ref(),
+                ptr::null_mut(),
+                ptr::null_mut(),
+                &mut error,
+            );
+            if json.buf.is_null() {
+                return Err(Error::fleece_error(error));
+            }
+            let doc = FLDoc_FromJSON(json.as_slice(), &mut error);
+            if doc.is_null() {
+                return Err(Error::fleece_error(error));
+            }
+            Ok(Self { cbl_ref: doc })
+        }
+    }
+
+    /// Encodes a copy of the value as a new, standalone document.
+    pub fn from_value<V: FleeceReference>(value: &V) -> Result<Self> {
+        unsafe {
+            let data = encode_value(value._fleece_ref())?;
+            let doc =
+                FLDoc_FromResultData(data, Trust::Trusted as u32, ptr::null_mut(), NULL_SLICE);
+            if doc.is_null() {
+                return Err(Error::fleece_error(FLError_kFLInvalidData));
+            }
+            Ok(Self { cbl_ref: doc })
+        }
+    }
+
     pub fn root(&self) -> Value {
         unsafe { Value::wrap(FLDoc_GetRoot(self.get_ref()), self) }
     }
@@ -110,6 +150,22 @@ impl Fleece {
         unsafe { FLDoc_GetData(self.get_ref()).as_byte_array().unwrap() }
     }
 
+    /// Returns a human-readable dump of the encoded data: the offset, raw bytes and
+    /// interpretation of every value in the buffer.
+    pub fn dump(&self) -> String {
+        unsafe {
+            FLData_Dump(FLDoc_GetData(self.get_ref()))
+                .to_string()
+                .unwrap_or_default()
+        }
+    }
+
+    /// Checks the encoded data. With `Trust::Untrusted` the whole buffer is validated, which is
+    /// useful for documents that were parsed as trusted.
+    pub fn validate(&self, trust: Trust) -> Result<()> {
+        unsafe { validate_data(FLDoc_GetData(self.get_ref()), trust) }
+    }
+
     pub fn wrap(doc: FLDoc) -> Self {
         Self {
             cbl_ref: unsafe { retain(doc) },
@@ -167,6 +223,58 @@ pub trait FleeceReference:
         unsafe { FLValue_ToJSON(self._fleece_ref()).to_string().unwrap() }
     }
 
+    /// Converts the value to JSON (or JSON5) in the given format.
+    fn to_json_with(&self, format: JsonFormat) -> String {
+        let json = unsafe {
+            FLValue_ToJSONX(self._fleece_ref(), format.json5, format.canonical)
+                .to_string()
+                .unwrap()
+        };
+        if format.pretty {
+            pretty_print(&json)
+        } else {
+            json
+        }
+    }
+
+    // Diagnostics:
+
+    /// Returns a human-readable dump of the value encoded as a standalone Fleece document.
+    fn dump(&self) -> Result<String> {
+        unsafe {
+            let data = encode_value(self._fleece_ref())?;
+            Ok(FLData_Dump(data.as_slice()).to_string().unwrap_or_default())
+        }
+    }
+
+    /// Validates the encoded data the value is read from: the whole Fleece document containing
+    /// it, see `Fleece::validate`. Mutable values aren't encoded, so they are always valid.
+    /// A value that isn't in a document, like `Value::UNDEFINED`, is `InvalidParameter`.
+    fn validate(&self, trust: Trust) -> Result<()> {
+        let value = self._fleece_ref();
+        if value.is_null() {
+            return Err(Error::cbl_error(CouchbaseLiteError::InvalidParameter));
+        }
+        unsafe {
+            if FLValue_IsMutable(value) {
+                return Ok(());
+            }
+            let doc = FLValue_FindDoc(value);
+            if doc.is_null() {
+                return Err(Error::cbl_error(CouchbaseLiteError::InvalidParameter));
+            }
+            let result = validate_data(FLDoc_GetData(doc), trust);
+            FLDoc_Release(doc);
+            result
+        }
+    }
+
+    /// Returns the number of bytes taken by the value and its children once encoded as a
+    /// standalone Fleece document.
+    fn encoded_size(&self) -> Result<usize> {
+        unsafe { encode_value(self._fleece_ref()).map(|data| data.size) }
+    }
+
     // Blob accessors:
 
     fn is_blob(&self) -> bool {
@@ -178,6 +286,110 @@ pub trait FleeceReference:
     }
 }
 
+/** Output options for `FleeceReference::to_json_with`. The default is the compact JSON
+produced by `to_json`. */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct JsonFormat {
+    /// Emit JSON5: dictionary keys are unquoted when they are valid identifiers.
+    pub json5: bool,
+    /// Emit the canonical form: dictionary keys are sorted and numbers are formatted
+    /// consistently, so equal values always produce the same text.
+    pub canonical: bool,
+    /// Indent nested collections with two spaces, one item per line.
+    pub pretty: bool,
+}
+
+impl JsonFormat {
+    pub const CANONICAL: Self = Self {
+        json5: false,
+        canonical: true,
+        pretty: false,
+    };
+    pub const PRETTY: Self = Self {
+        json5: false,
+        canonical: false,
+        pretty: true,
+    };
+    pub const JSON5: Self = Self {
+        json5: true,
+        canonical: false,
+        pretty: false,
+    };
+}
+
+// Re-indents compact JSON/JSON5, as produced by Fleece, leaving strings untouched.
+fn pretty_print(json: &str) -> String {
+    fn new_line(out: &mut String, depth: usize) {
+        out.push('\n');
+        out.extend(std::iter::repeat_n("  ", depth));
+    }
+
+    let mut out = String::with_capacity(json.len() * 2);
+    let mut depth = 0;
+    let mut quote = None;
+    let mut escaped = false;
+    let mut chars = json.chars().peekable();
+    while let Some(c) = chars.next() {
+        out.push(c);
+        if let Some(q) = quote {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == q {
+                quote = None;
+            }
+            if quote.is_some() {
+                continue;
+            }
+        } else {
+            match c {
+                '"' | '\'' => quote = Some(c),
+                '[' | '{' => {
+                    if matches!(chars.peek(), Some(']' | '}')) {
+                        out.extend(chars.next());
+                    } else {
+                        depth += 1;
+                        new_line(&mut out, depth);
+                    }
+                }
+                ',' => new_line(&mut out, depth),
+                ':' => out.push(' '),
+                _ => {}
+            }
+        }
+        if quote.is_none() && depth > 0 && matches!(chars.peek(), Some(']' | '}')) {
+            depth -= 1;
+            new_line(&mut out, depth);
+        }
+    }
+    out
+}
+
+unsafe fn encode_value(value: FLValue) -> Result<FLSliceResult> {
+    if value.is_null() {
+        return Err(Error::cbl_error(CouchbaseLiteError::InvalidParameter));
+    }
+    unsafe {
+        let encoder = FLEncoder_New();
+        FLEncoder_WriteValue(encoder, value);
+        let mut error: FLError = 0;
+        let data = FLEncoder_Finish(encoder, &mut error);
+        FLEncoder_Free(encoder);
+        if data.buf.is_null() {
+            return Err(Error::fleece_error(error));
+        }
+        Ok(data)
+    }
+}
+
+unsafe fn validate_data(data: FLSlice, trust: Trust) -> Result<()> {
+    if unsafe { FLValue_FromData(data, trust as u32) }.is_null() {
+        return Err(Error::fleece_error(FLError_kFLInvalidData));
+    }
+    Ok(())
+}
+
 /** A Fleece value. It could be any type, including Undefined (empty). */
 #[derive(Clone, Copy)]
 pub struct Value {
@@ -285,10 +497,13 @@ impl Value {
         unsafe { FLValue_AsBool(self.get_ref()) }
     }
 
+    /// Converts the value to a timestamp: an ISO-8601 string is parsed (like the SQL++ function
+    /// `STR_TO_MILLIS`), and a number is interpreted as milliseconds since the Unix epoch.
+    /// Returns None if the value is neither, like Fleece's `FLTimestampNone`.
     pub fn as_timestamp(&self) -> Option<Timestamp> {
         unsafe {
             let t = FLValue_AsTimestamp(self.get_ref());
-            if t == 0 {
+            if t == TIMESTAMP_NONE {
                 return None;
             }
             Some(Timestamp::new(t))
@@ -348,9 +563,12 @@ impl Default for Value {
     }
 }
 
+/// Equality of the SQL++ collation, consistent with `Ord` and `Hash`: numbers are equal when
+/// their exact values are, so `1 == 1.0`, and NaN equals NaN. (`FLValue_IsEqual` compares
+/// numbers as doubles, and NaN as different from itself, which `Eq` doesn't allow.)
 impl PartialEq for Value {
     fn eq(&self, other: &Self) -> bool {
-        unsafe { FLValue_IsEqual(self.get_ref(), other.get_ref()) }
+        equal(self, other)
     }
 }
 
@@ -702,12 +920,9 @@ impl DictIterator {
     pub fn count(&self) -> u32 {
         unsafe { FLDictIterator_GetCount(&self.innards) }
     }
-}
 
-impl Iterator for DictIterator {
-    type Item = (String, Value);
-
-    fn next(&mut self) -> Option<Self::Item> {
+    // The next entry, with the key borrowed from the dict instead of copied.
+    fn next_entry<'d>(&mut self) -> Option<(&'d str, Value)> {
         unsafe {
             let val = FLDictIterator_GetValue(&self.innards);
             if val.is_null() {
@@ -717,11 +932,20 @@ impl Iterator for DictIterator {
                 .as_str()
                 .unwrap_or_default();
             FLDictIterator_Next(&mut self.innards);
-            Some((key.to_string(), Value { cbl_ref: val }))
+            Some((key, Value { cbl_ref: val }))
         }
     }
 }
 
+impl Iterator for DictIterator {
+    type Item = (String, Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_entry()
+            .map(|(key, value)| (key.to_string(), value))
+    }
+}
+
 impl std::iter::FusedIterator for DictIterator {}
 
 impl ExactSizeIterator for DictIterator {
@@ -745,3 +969,258 @@ impl std::iter::FromIterator<(String, Value)> for MutableDict {
         mut_dict
     }
 }
+
+//////// COLLATION & HASHING
+
+impl ValueType {
+    /// Position of the type in the SQL++ collation order:
+    /// MISSING < NULL < FALSE < TRUE < numbers < strings < arrays < objects < binary.
+    const fn collation_rank(self) -> u8 {
+        match self {
+            Self::Undefined => 0,
+            Self::Null => 1,
+            Self::Bool => 2,
+            Self::Number => 3,
+            Self::String => 4,
+            Self::Array => 5,
+            Self::Dict => 6,
+            Self::Data => 7,
+        }
+    }
+}
+
+/// A number read from a Fleece value, keeping integers exact.
+enum Number {
+    Int(i128),
+    Double(f64),
+}
+
+impl Number {
+    fn of(value: &Value) -> Self {
+        if value.is_unsigned() {
+            Self::Int(i128::from(value.as_u64_or_0()))
+        } else if value.is_integer() {
+            Self::Int(i128::from(value.as_i64_or_0()))
+        } else {
+            Self::Double(value.as_f64_or_0())
+        }
+    }
+
+    /// Doubles with an integral value are folded into `Int`, so that `1` and `1.0` collate
+    /// and hash identically, and all NaNs into one NaN, greater than the other numbers.
+    fn normalized(self) -> Self {
+        match self {
+            Self::Double(d) if d.is_nan() => Self::Double(f64::NAN),
+            Self::Double(d) if d.fract() == 0.0 && d.abs() < I128_BOUND => Self::Int(d as i128),
+            n => n,
+        }
+    }
+
+    fn cmp(&self, other: &Self) -> Ordering {
+        match (self, other) {
+            (Self::Int(a), Self::Int(b)) => a.cmp(b),
+            (Self::Double(a), Self::Double(b)) => a.total_cmp(b),
+            (Self::Int(a), Self::Double(b)) => cmp_int_double(*a, *b),
+            (Self::Double(a), Self::Int(b)) => cmp_int_double(*b, *a).reverse(),
+        }
+    }
+}
+
+const I128_BOUND: f64 = 170_141_183_460_469_231_731_687_303_715_884_105_728.0; // 2^127
+
+fn cmp_int_double(i: i128, d: f64) -> Ordering {
+    if d.is_nan() || d >= I128_BOUND {
+        return Ordering::Less;
+    }
+    if d < -I128_BOUND {
+        return Ordering::Greater;
+    }
+    let integral = d.trunc();
+    i.cmp(&(integral as i128))
+        .then_with(|| 0.0_f64.total_cmp(&(d - integral)))
+}
+
+/// The entries of a dict, with the keys borrowed from it.
+struct DictEntries<'d> {
+    iter: DictIterator,
+    _dict: PhantomData<&'d Dict>,
+}
+
+impl<'d> Iterator for DictEntries<'d> {
+    type Item = (&'d str, Value);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.iter.next_entry()
+    }
+}
+
+fn entries(dict: &Dict) -> DictEntries<'_> {
+    DictEntries {
+        iter: dict.iter(),
+        _dict: PhantomData,
+    }
+}
+
+/// Returns the entries of a dict sorted by key, regardless of how they are laid out in memory
+/// (shared keys are not stored in alphabetical order.)
+fn sorted_entries(dict: &Dict) -> Vec<(&str, Value)> {
+    let mut entries: Vec<(&str, Value)> = entries(dict).collect();
+    entries.sort_by(|a, b| a.0.cmp(b.0));
+    entries
+}
+
+/// Same as `collate(a, b).is_eq()`, without sorting the entries of dicts: the entries of one
+/// dict are looked up in the other.
+fn equal(a: &Value, b: &Value) -> bool {
+    match (a.get_type(), b.get_type()) {
+        (ValueType::Array, ValueType::Array) => {
+            let (array_a, array_b) = (a.as_array(), b.as_array());
+            array_a.count() == array_b.count()
+                && array_a
+                    .iter()
+                    .zip(array_b.iter())
+                    .all(|(x, y)| equal(&x, &y))
+        }
+        (ValueType::Dict, ValueType::Dict) => {
+            let (dict_a, dict_b) = (a.as_dict(), b.as_dict());
+            dict_a.count() == dict_b.count()
+                && entries(&dict_a).all(|(key, x)| {
+                    let y = dict_b.get(key);
+                    y.get_type() != ValueType::Undefined && equal(&x, &y)
+                })
+        }
+        _ => collate(a, b).is_eq(),
+    }
+}
+
+fn collate(a: &Value, b: &Value) -> Ordering {
+    let (type_a, type_b) = (a.get_type(), b.get_type());
+    if type_a != type_b {
+        return type_a.collation_rank().cmp(&type_b.collation_rank());
+    }
+    match type_a {
+        ValueType::Undefined | ValueType::Null => Ordering::Equal,
+        ValueType::Bool => a.as_bool_or_false().cmp(&b.as_bool_or_false()),
+        ValueType::Number => Number::of(a).normalized().cmp(&Number::of(b).normalized()),
+        ValueType::String => a.as_string().cmp(&b.as_string()),
+        ValueType::Data => a.as_data().cmp(&b.as_data()),
+        ValueType::Array => {
+            let (array_a, array_b) = (a.as_array(), b.as_array());
+            array_a
+                .iter()
+                .zip(array_b.iter())
+                .map(|(x, y)| collate(&x, &y))
+                .find(|o| o.is_ne())
+                .unwrap_or_else(|| array_a.count().cmp(&array_b.count()))
+        }
+        ValueType::Dict => {
+            let (dict_a, dict_b) = (a.as_dict(), b.as_dict());
+            dict_a.count().cmp(&dict_b.count()).then_with(|| {
+                let (entries_a, entries_b) = (sorted_entries(&dict_a), sorted_entries(&dict_b));
+                entries_a
+                    .iter()
+                    .zip(&entries_b)
+                    .map(|(x, y)| x.0.cmp(y.0))
+                    .find(|o| o.is_ne())
+                    .or_else(|| {
+                        entries_a
+                            .iter()
+                            .zip(&entries_b)
+                            .map(|(x, y)| collate(&x.1, &y.1))
+                            .find(|o| o.is_ne())
+                    })
+                    .unwrap_or(Ordering::Equal)
+            })
+        }
+    }
+}
+
+fn hash_value<H: Hasher>(value: &Value, state: &mut H) {
+    let value_type = value.get_type();
+    value_type.collation_rank().hash(state);
+    match value_type {
+        ValueType::Undefined | ValueType::Null => {}
+        ValueType::Bool => value.as_bool_or_false().hash(state),
+        ValueType::Number => match Number::of(value).normalized() {
+            Number::Int(i) => i.hash(state),
+            Number::Double(d) => d.to_bits().hash(state),
+        },
+        ValueType::String => value.as_string().hash(state),
+        ValueType::Data => value.as_data().hash(state),
+        ValueType::Array => {
+            let array = value.as_array();
+            array.count().hash(state);
+            array.iter().for_each(|v| hash_value(&v, state));
+        }
+        ValueType::Dict => {
+            let dict = value.as_dict();
+            dict.count().hash(state);
+            // The entry hashes are summed, so that the order of the entries doesn't matter.
+            entries(&dict)
+                .map(|(key, v)| {
+                    let mut hasher = DefaultHasher::new();
+                    key.hash(&mut hasher);
+                    hash_value(&v, &mut hasher);
+                    hasher.finish()
+                })
+                .fold(0_u64, u64::wrapping_add)
+                .hash(state);
+        }
+    }
+}
+
+/// Deep structural hash, consistent with `PartialEq`.
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        hash_value(self, state);
+    }
+}
+
+/// Total order following the SQL++ collation of values.
+impl Ord for Value {
+    fn cmp(&self, other: &Self) -> Ordering {
+        collate(self, other)
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Hash for Array {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_value().hash(state);
+    }
+}
+
+impl Ord for Array {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_value().cmp(&other.as_value())
+    }
+}
+
+impl PartialOrd for Array {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Hash for Dict {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.as_value().hash(state);
+    }
+}
+
+impl Ord for Dict {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_value().cmp(&other.as_value())
+    }
+}
+
+impl PartialOrd for Dict {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
@@ -16,15 +16,16 @@
 //
 
 use crate::{
-    CblRef, CouchbaseLiteError, Error, ErrorCode, Result,
+    CblRef, CouchbaseLiteError, Error, ErrorCode, Result, Timestamp,
     slice::{from_bytes, from_str},
     c_api::{
         FLArray_AsMutable, FLArray_MutableCopy, FLDict_AsMutable, FLDict_MutableCopy,
         FLMutableArray, FLMutableArray_Append, FLMutableArray_Insert, FLMutableArray_IsChanged,
         FLMutableArray_New, FLMutableArray_Remove, FLMutableArray_Set, FLMutableDict,
-        FLMutableDict_IsChanged, FLMutableDict_New, FLMutableDict_Remove, FLMutableDict_RemoveAll,
-        FLMutableDict_Set, FLSlot, FLSlot_SetBool, FLSlot_SetDouble, FLSlot_SetInt, FLSlot_SetNull,
-        FLSlot_SetString, FLSlot_SetValue, FLValue, FLValue_Release, FLValue_Retain,
+        FLMutableDict_IsChanged, FLMutableDict_New, FLMutableDict_NewFromJSON,
+        FLMutableDict_Remove, FLMutableDict_RemoveAll, FLMutableDict_Set, FLSlot, FLSlot_SetBool,
+        FLSlot_SetDouble, FLSlot_SetInt, FLSlot_SetNull, FLSlot_SetString, FLSlot_SetValue,
+        FLTimestamp_ToString, FLValue, FLValue_Release, FLValue_Retain, FLError,
     },
     fleece::{Array, ArrayIterator, Dict, DictIterator, DictKey, FleeceReference, Value},
 };
@@ -267,6 +268,18 @@ impl MutableDict {
         }
     }
 
+    /// Creates a dictionary by parsing a JSON object.
+    pub fn from_json(json: &str) -> Result<Self> {
+        unsafe {
+            let mut error: FLError = 0;
+            let dict = FLMutableDict_NewFromJSON(from_str(json).get_ref(), &mut error);
+            if dict.is_null() {
+                return Err(Error::fleece_error(error));
+            }
+            Ok(Self { cbl_ref: dict })
+        }
+    }
+
     pub fn from_dict(dict: &Dict) -> Self {
         Self::from_dict_(dict, CopyFlags::Default)
     }
@@ -486,6 +499,17 @@ impl Slot<'_> {
         unsafe { FLSlot_SetString(self.get_ref(), from_bytes(value.as_ref()).get_ref()) }
     }
 
+    /// Stores a timestamp as an ISO-8601 UTC string, the canonical form expected by the SQL++
+    /// date functions (`STR_TO_MILLIS`, `DATE_DIFF_STR`...)
+    pub fn put_timestamp(self, value: Timestamp) {
+        unsafe {
+            FLSlot_SetString(
+                self.get_ref(),
+                FLTimestamp_ToString(value.timestamp, true).as_slice(),
+            )
+        }
+    }
+
     pub fn put_value<VALUE: FleeceReference>(self, value: &VALUE) {
         unsafe { FLSlot_SetValue(self.get_ref(), value._fleece_ref()) }
     }
@@ -0,0 +1,470 @@
+//! Highlights the words of full-text search results that match the search terms, and extracts
+//! snippets around them. Words are compared like the full-text index does: case-insensitively,
+//! without accents if the index ignores them, and by their Snowball stem if the index has a
+//! language. The stop words the index leaves out with a language aren't: a stop word among the
+//! search terms is highlighted, although the search ignores it.
+
+use crate::{CouchbaseLiteError, Error, Result, Row};
+use rust_stemmers::{Algorithm, Stemmer};
+use std::ops::Range;
+
+/** How the text of the searched documents was indexed, see `FullTextIndexConfiguration`. */
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TextOptions {
+    /** The language of the index, as an ISO-639 code or an English name: one of the
+    languages Couchbase Lite stems, Danish ("da"), Dutch ("nl"), English ("en"), Finnish ("fi"),
+    French ("fr"), German ("de"), Hungarian ("hu"), Italian ("it"), Norwegian ("no"),
+    Portuguese ("pt"), Romanian ("ro"), Russian ("ru"), Spanish ("es"), Swedish ("sv") or
+    Turkish ("tr"). Words are compared by stem; without language, as they are. Unlike the
+    index, the highlighter doesn't leave out the stop words of the language. */
+    pub language: Option<String>,
+    /** Whether the index ignores accents. */
+    pub ignore_accents: bool,
+}
+
+/** The marks and length of highlighted snippets. */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnippetOptions {
+    /** The maximum number of words of a snippet. */
+    pub max_words: usize,
+    /** Inserted before each matching word. */
+    pub start_mark: String,
+    /** Inserted after each matching word. */
+    pub end_mark: String,
+    /** Replaces the text cut at the start or the end of a snippet. */
+    pub ellipsis: String,
+}
+
+impl Default for SnippetOptions {
+    fn default() -> Self {
+        Self {
+            max_words: 15,
+            start_mark: "<b>".to_string(),
+            end_mark: "</b>".to_string(),
+            ellipsis: "…".to_string(),
+        }
+    }
+}
+
+/** Finds the words of a text matching search terms. */
+#[derive(Debug, Clone)]
+pub struct Highlighter {
+    terms: Vec<Term>,
+    stemmer: Option<WordStemmer>,
+    ignore_accents: bool,
+}
+
+// A Snowball stemmer, which can be cloned.
+struct WordStemmer {
+    algorithm: Algorithm,
+    stemmer: Stemmer,
+}
+
+impl WordStemmer {
+    fn new(algorithm: Algorithm) -> Self {
+        Self {
+            algorithm,
+            stemmer: Stemmer::create(algorithm),
+        }
+    }
+}
+
+impl Clone for WordStemmer {
+    fn clone(&self) -> Self {
+        Self::new(self.algorithm)
+    }
+}
+
+impl std::fmt::Debug for WordStemmer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.algorithm.fmt(f)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Term {
+    normalized: String,
+    prefix: bool,
+}
+
+impl Highlighter {
+    /** Creates a highlighter for search terms; a term ending with `*` matches the words it
+    prefixes. Fails with `InvalidParameter` if the language isn't supported. */
+    pub fn for_terms<S: AsRef<str>>(terms: &[S], options: &TextOptions) -> Result<Self> {
+        let mut highlighter = Self::new(options)?;
+        for term in terms {
+            let prefix = term.as_ref().trim_end().ends_with('*');
+            let words: Vec<_> = words(term.as_ref()).collect();
+            for (i, (_, word)) in words.iter().enumerate() {
+                highlighter.add_term(word, prefix && i + 1 == words.len());
+            }
+        }
+        Ok(highlighter)
+    }
+
+    /** Creates a highlighter for the search string of a `MATCH()` query, like
+    `'travel AND (ski* OR snow)'`. The operators, the column filters and the excluded terms
+    (after `NOT` or `-`) are ignored, and the words of a phrase are matched independently.
+    Fails with `InvalidParameter` if the language isn't supported. */
+    pub fn for_match(expression: &str, options: &TextOptions) -> Result<Self> {
+        let mut highlighter = Self::new(options)?;
+        let mut excluded_depth: Option<usize> = None;
+        let mut exclude_next = false;
+        let mut depth = 0;
+        for token in match_tokens(expression) {
+            match token {
+                MatchToken::Open => {
+                    if exclude_next && excluded_depth.is_none() {
+                        excluded_depth = Some(depth);
+                    }
+                    exclude_next = false;
+                    depth += 1;
+                }
+                MatchToken::Close => {
+                    depth = depth.saturating_sub(1);
+                    if excluded_depth == Some(depth) {
+                        excluded_depth = None;
+                    }
+                }
+                MatchToken::Not => exclude_next = true,
+                MatchToken::Operator => {}
+                MatchToken::Terms {
+                    text,
+                    prefix,
+                    excluded,
+                } => {
+                    if excluded_depth.is_none() && !excluded && !exclude_next {
+                        let words: Vec<_> = words(text).collect();
+                        for (i, (_, word)) in words.iter().enumerate() {
+                            highlighter.add_term(word, prefix && i + 1 == words.len());
+                        }
+                    }
+                    exclude_next = false;
+                }
+            }
+        }
+        Ok(highlighter)
+    }
+
+    fn new(options: &TextOptions) -> Result<Self> {
+        let stemmer = options
+            .language
+            .as_deref()
+            .map(|language| {
+                stemmer(language)
+                    .map(WordStemmer::new)
+                    .ok_or(Error::cbl_error(CouchbaseLiteError::InvalidParameter))
+            })
+            .transpose()?;
+        Ok(Self {
+            terms: Vec::new(),
+            stemmer,
+            ignore_accents: options.ignore_accents,
+        })
+    }
+
+    fn add_term(&mut self, word: &str, prefix: bool) {
+        // The prefix of a word isn't stemmed, like in the index.
+        let normalized = if prefix {
+            fold(word, self.ignore_accents)
+        } else {
+            self.normalize(word)
+        };
+        if !normalized.is_empty() {
+            self.terms.push(Term { normalized, prefix });
+        }
+    }
+
+    fn normalize(&self, word: &str) -> String {
+        let folded = fold(word, self.ignore_accents);
+        match &self.stemmer {
+            Some(stemmer) => stemmer.stemmer.stem(&folded).into_owned(),
+            None => folded,
+        }
+    }
+
+    fn is_match(&self, word: &str) -> bool {
+        let normalized = self.normalize(word);
+        self.terms.iter().any(|term| {
+            if term.prefix {
+                normalized.starts_with(&term.normalized)
+                    || fold(word, self.ignore_accents).starts_with(&term.normalized)
+            } else {
+                normalized == term.normalized
+            }
+        })
+    }
+
+    /** Returns true if there is no term to match. */
+    pub fn is_empty(&self) -> bool {
+        self.terms.is_empty()
+    }
+
+    /** Returns the byte ranges of the words of the text that match a term. */
+    pub fn matches(&self, text: &str) -> Vec<Range<usize>> {
+        words(text)
+            .filter(|(_, word)| self.is_match(word))
+            .map(|(range, _)| range)
+            .collect()
+    }
+
+    /** Returns the whole text with the matching words marked. */
+    pub fn highlight(&self, text: &str, options: &SnippetOptions) -> String {
+        mark(text, 0..text.len(), &self.matches(text), options)
+    }
+
+    /** Returns the part of the text of at most `max_words` words with the most matching
+    words, with the matching words marked. Without match, returns the start of the text. */
+    pub fn snippet(&self, text: &str, options: &SnippetOptions) -> String {
+        let words: Vec<(Range<usize>, bool)> = words(text)
+            .map(|(range, word)| (range, self.is_match(word)))
+            .collect();
+        let max_words = options.max_words.max(1);
+        if words.is_empty() {
+            return String::new();
+        }
+
+        // Windows starting a third of their length before a match:
+        let mut best = (0, 0);
+        for (i, _) in words
+            .iter()
+            .enumerate()
+            .filter(|(_, (_, matched))| *matched)
+        {
+            let start = i
+                .saturating_sub(max_words / 3)
+                .min(words.len().saturating_sub(max_words));
+            let end = (start + max_words).min(words.len());
+            let count = words[start..end].iter().filter(|(_, m)| *m).count();
+            if count > best.1 {
+                best = (start, count);
+            }
+        }
+        let start = best.0;
+        let end = (start + max_words).min(words.len());
+
+        let range = if start == 0 { 0 } else { words[start].0.start }..if end == words.len() {
+            text.len()
+        } else {
+            words[end - 1].0.end
+        };
+        let matches: Vec<Range<usize>> = words[start..end]
+            .iter()
+            .filter(|(_, matched)| *matched)
+            .map(|(range, _)| range.clone())
+            .collect();
+
+        let mut snippet = String::new();
+        if start > 0 {
+            snippet.push_str(&options.ellipsis);
+        }
+        snippet.push_str(&mark(text, range, &matches, options));
+        if end < words.len() {
+            snippet.push_str(&options.ellipsis);
+        }
+        snippet
+    }
+
+    /** Returns the matching words of a column of a row, if it is a string. */
+    pub fn row_matches(&self, row: &Row, column: &str) -> Option<Vec<Range<usize>>> {
+        row.get_key(column)
+            .as_string()
+            .map(|text| self.matches(text))
+    }
+
+    /** Returns the snippet of a column of a row, if it is a string. */
+    pub fn row_snippet(&self, row: &Row, column: &str, options: &SnippetOptions) -> Option<String> {
+        row.get_key(column)
+            .as_string()
+            .map(|text| self.snippet(text, options))
+    }
+}
+
+// Copies the range of the text with the matches inside it marked.
+fn mark(
+    text: &str,
+    range: Range<usize>,
+    matches: &[Range<usize>],
+    options: &SnippetOptions,
+) -> String {
+    let mut marked = String::new();
+    let mut position = range.start;
+    for matched in matches
+        .iter()
+        .filter(|m| m.start >= range.start && m.end <= range.end)
+    {
+        marked.push_str(&text[position..matched.start]);
+        marked.push_str(&options.start_mark);
+        marked.push_str(&text[matched.clone()]);
+        marked.push_str(&options.end_mark);
+        position = matched.end;
+    }
+    marked.push_str(&text[position..range.end]);
+    marked
+}
+
+// The words of a text, runs of letters and digits, with their byte range.
+fn words(text: &str) -> impl Iterator<Item = (Range<usize>, &str)> {
+    let mut chars = text.char_indices().peekable();
+    std::iter::from_fn(move || {
+        let start = loop {
+            let (i, c) = chars.next()?;
+            if c.is_alphanumeric() {
+                break i;
+            }
+        };
+        let mut end = text.len();
+        while let Some(&(i, c)) = chars.peek() {
+            if !c.is_alphanumeric() {
+                end = i;
+                break;
+            }
+            chars.next();
+        }
+        Some((start..end, &text[start..end]))
+    })
+}
+
+enum MatchToken<'a> {
+    Open,
+    Close,
+    Not,
+    Operator,
+    Terms {
+        text: &'a str,
+        prefix: bool,
+        excluded: bool,
+    },
+}
+
+// Splits a full-text search string into terms, phrases, operators and parentheses.
+fn match_tokens(expression: &str) -> Vec<MatchToken<'_>> {
+    let mut tokens = Vec::new();
+    let mut rest = expression;
+    loop {
+        rest = rest.trim_start();
+        let Some(c) = rest.chars().next() else {
+            break;
+        };
+        match c {
+            '(' | ')' => {
+                tokens.push(if c == '(' {
+                    MatchToken::Open
+                } else {
+                    MatchToken::Close
+                });
+                rest = &rest[1..];
+                continue;
+            }
+            _ => {}
+        }
+
+        let excluded = rest.starts_with('-');
+        if excluded {
+            rest = &rest[1..];
+        }
+        // A column filter, like `title:word`:
+        let end = rest
+            .find(|c: char| c.is_whitespace() || c == '(' || c == ')' || c == '"')
+            .unwrap_or(rest.len());
+        if let Some(colon) = rest[..end].find(':') {
+            rest = &rest[colon + 1..];
+        }
+        rest = rest.strip_prefix('^').unwrap_or(rest);
+
+        if let Some(phrase) = rest.strip_prefix('"') {
+            let end = phrase.find('"').unwrap_or(phrase.len());
+            let after = &phrase[(end + 1).min(phrase.len())..];
+            let prefix = after.starts_with('*');
+            tokens.push(MatchToken::Terms {
+                text: &phrase[..end],
+                prefix,
+                excluded,
+            });
+            rest = after.strip_prefix('*').unwrap_or(after);
+            continue;
+        }
+
+        let end = rest
+            .find(|c: char| c.is_whitespace() || c == '(' || c == ')')
+            .unwrap_or(rest.len());
+        let word = &rest[..end];
+        rest = &rest[end..];
+        match word {
+            "NOT" => tokens.push(MatchToken::Not),
+            "AND" | "OR" => tokens.push(MatchToken::Operator),
+            _ if word == "NEAR" || word.starts_with("NEAR/") => {
+                tokens.push(MatchToken::Operator);
+            }
+            _ => tokens.push(MatchToken::Terms {
+                text: word.trim_end_matches('*'),
+                prefix: word.ends_with('*'),
+                excluded,
+            }),
+        }
+    }
+    tokens
+}
+
+// Lowercases a word, and removes its accents if requested.
+fn fold(word: &str, ignore_accents: bool) -> String {
+    let mut folded = String::with_capacity(word.len());
+    for c in word.chars().flat_map(char::to_lowercase) {
+        match remove_accent(c).filter(|_| ignore_accents) {
+            Some(base) => folded.push_str(base),
+            None => folded.push(c),
+        }
+    }
+    folded
+}
+
+// The unaccented form of the lowercase Latin letters with diacritics.
+fn remove_accent(c: char) -> Option<&'static str> {
+    Some(match c {
+        'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' | 'ā' | 'ă' | 'ą' => "a",
+        'æ' => "ae",
+        'ç' | 'ć' | 'ĉ' | 'ċ' | 'č' => "c",
+        'ď' | 'đ' => "d",
+        'è' | 'é' | 'ê' | 'ë' | 'ē' | 'ĕ' | 'ė' | 'ę' | 'ě' => "e",
+        'ĝ' | 'ğ' | 'ġ' | 'ģ' => "g",
+        'ĥ' | 'ħ' => "h",
+        'ì' | 'í' | 'î' | 'ï' | 'ĩ' | 'ī' | 'ĭ' | 'į' | 'ı' => "i",
+        'ĵ' => "j",
+        'ķ' => "k",
+        'ĺ' | 'ļ' | 'ľ' | 'ŀ' | 'ł' => "l",
+        'ñ' | 'ń' | 'ņ' | 'ň' => "n",
+        'ò' | 'ó' | 'ô' | 'õ' | 'ö' | 'ø' | 'ō' | 'ŏ' | 'ő' => "o",
+        'œ' => "oe",
+        'ŕ' | 'ŗ' | 'ř' => "r",
+        'ś' | 'ŝ' | 'ş' | 'š' => "s",
+        'ß' => "ss",
+        'ţ' | 'ť' | 'ŧ' => "t",
+        'ù' | 'ú' | 'û' | 'ü' | 'ũ' | 'ū' | 'ŭ' | 'ů' | 'ű' | 'ų' => "u",
+        'ŵ' => "w",
+        'ý' | 'ÿ' | 'ŷ' => "y",
+        'ź' | 'ż' | 'ž' => "z",
+        _ => return None,
+    })
+}
+
+// The Snowball stemmer of a language stemmed by Couchbase Lite, by ISO-639 code or name.
+fn stemmer(language: &str) -> Option<Algorithm> {
+    Some(match language.to_ascii_lowercase().as_str() {
+        "da" | "danish" => Algorithm::Danish,
+        "nl" | "dutch" => Algorithm::Dutch,
+        "en" | "english" => Algorithm::English,
+        "fi" | "finnish" => Algorithm::Finnish,
+        "fr" | "french" => Algorithm::French,
+        "de" | "german" => Algorithm::German,
+        "hu" | "hungarian" => Algorithm::Hungarian,
+        "it" | "italian" => Algorithm::Italian,
+        "no" | "norwegian" => Algorithm::Norwegian,
+        "pt" | "portuguese" => Algorithm::Portuguese,
+        "ro" | "romanian" => Algorithm::Romanian,
+        "ru" | "russian" => Algorithm::Russian,
+        "es" | "spanish" => Algorithm::Spanish,
+        "sv" | "swedish" => Algorithm::Swedish,
+        "tr" | "turkish" => Algorithm::Turkish,
+        _ => return None,
+    })
+}
@@ -5,17 +5,38 @@ use crate::{
         CBLDatabase_CreateValueIndex, CBLCollection_CreateValueIndex, CBLCollection_DeleteIndex,
         CBLCollection_GetIndexNames, CBLCollection_CreateArrayIndex, CBLArrayIndexConfiguration,
         CBLQueryIndex, CBLQueryIndex_Name, CBLQueryIndex_Collection, CBLCollection_GetIndex,
+        CBLCollection_CreateFullTextIndex, CBLFullTextIndexConfiguration,
     },
     error::{Error, Result, failure},
-    slice::{from_str, from_c_str, Slice},
-    QueryLanguage, Array,
+    slice::{from_str, from_c_str, Slice, NULL_SLICE},
+    QueryLanguage, Array, Dict, MutableDict, ValueType,
     collection::Collection,
-    check_error, release, retain, CouchbaseLiteError,
+    document::{Update, UpdateOptions, not_found_as_none},
+    check_error, release, retain, CouchbaseLiteError, query_cache,
 };
+#[cfg(feature = "enterprise")]
+use crate::{
+    check_bool,
+    c_api::{
+        CBLVectorIndexConfiguration, CBLVectorEncoding, CBLVectorEncoding_CreateNone,
+        CBLVectorEncoding_CreateScalarQuantizer, CBLVectorEncoding_CreateProductQuantizer,
+        CBLVectorEncoding_Free, CBLCollection_CreateVectorIndex, CBL_EnableVectorSearch, kCBLSQ4,
+        kCBLSQ6, kCBLSQ8, kCBLDistanceMetricEuclideanSquared, kCBLDistanceMetricCosine,
+        kCBLDistanceMetricEuclidean, kCBLDistanceMetricDot, kCBLDefaultVectorIndexLazy,
+        kCBLDefaultVectorIndexDistanceMetric, kCBLDefaultVectorIndexMinTrainingSize,
+        kCBLDefaultVectorIndexMaxTrainingSize, kCBLDefaultVectorIndexNumProbes,
+    },
+};
+#[cfg(feature = "enterprise")]
+use enum_primitive::FromPrimitive;
+#[cfg(feature = "enterprise")]
+use std::path::Path;
+use std::collections::HashSet;
 use std::ffi::CString;
 
 pub struct ValueIndexConfiguration {
     cbl_ref: CBLValueIndexConfiguration,
+    definition: IndexDefinition,
 }
 
 impl CblRef for ValueIndexConfiguration {
@@ -32,13 +53,21 @@ impl ValueIndexConfiguration {
     /// in a JSON Array or in N1QL syntax using comma delimiter.
     /// The where clause is optional and is a predicate expression defining conditions for indexing documents.
     pub fn new(query_language: QueryLanguage, expressions: &str, where_: Option<&str>) -> Self {
-        let expressions_slices = from_str(expressions);
-        let where_slices = from_str(where_.unwrap_or_default());
+        let expressions = expressions.to_string();
+        let where_ = where_.map(str::to_string);
+        // The slices point to the buffers of the strings, which stay in place when the strings
+        // are moved to the definition.
+        let cbl_ref = CBLValueIndexConfiguration {
+            expressionLanguage: query_language as u32,
+            expressions: from_str(&expressions).get_ref(),
+            where_: from_str(where_.as_deref().unwrap_or_default()).get_ref(),
+        };
         Self {
-            cbl_ref: CBLValueIndexConfiguration {
-                expressionLanguage: query_language as u32,
-                expressions: expressions_slices.get_ref(),
-                where_: where_slices.get_ref(),
+            cbl_ref,
+            definition: IndexDefinition::Value {
+                query_language,
+                expressions,
+                where_,
             },
         }
     }
@@ -51,6 +80,7 @@ pub struct ArrayIndexConfiguration {
     cbl_ref: CBLArrayIndexConfiguration,
     _path: Slice<CString>,
     _expressions: Slice<CString>,
+    definition: IndexDefinition,
 }
 
 impl CblRef for ArrayIndexConfiguration {
@@ -130,10 +160,703 @@ impl ArrayIndexConfiguration {
             },
             _path: path_s,
             _expressions: expressions_s,
+            definition: IndexDefinition::Array {
+                query_language,
+                path: path.to_string(),
+                expressions: expressions.to_string(),
+            },
+        })
+    }
+}
+
+/// Full-Text Index Configuration, for use with the MATCH() and RANK() query functions.
+#[derive(Debug)]
+pub struct FullTextIndexConfiguration {
+    cbl_ref: CBLFullTextIndexConfiguration,
+    _expressions: Slice<CString>,
+    _language: Option<Slice<CString>>,
+    _where: Option<Slice<CString>>,
+    definition: IndexDefinition,
+}
+
+impl CblRef for FullTextIndexConfiguration {
+    type Output = CBLFullTextIndexConfiguration;
+    fn get_ref(&self) -> Self::Output {
+        self.cbl_ref
+    }
+}
+
+impl FullTextIndexConfiguration {
+    /// Create a Full-Text Index Configuration.
+    ///   - query_langage:  The language used in the expressions (Required).
+    ///   - expressions:  The expressions of the text to index, in a JSON Array or in N1QL
+    ///     syntax using comma delimiter (Required).
+    ///   - ignore_accents:  Whether diacritical marks (accents) are ignored, so that "café"
+    ///     matches "cafe".
+    ///   - language:  The dominant language, as an ISO-639 code like "en" or a name like
+    ///     "english", which enables word stemming and stop-word removal. Without language, or
+    ///     with an unrecognized one, words are only matched as they are.
+    ///   - where_:  An optional predicate expression defining conditions for indexing documents.
+    pub fn new(
+        query_language: QueryLanguage,
+        expressions: &str,
+        ignore_accents: bool,
+        language: Option<&str>,
+        where_: Option<&str>,
+    ) -> Result<Self> {
+        let owned = |s: &str| {
+            CString::new(s)
+                .map(|c| from_c_str(c, s.len()))
+                .map_err(|_| Error::cbl_error(CouchbaseLiteError::InvalidParameter))
+        };
+        let expressions_s = owned(expressions)?;
+        let language_s = language.map(owned).transpose()?;
+        let where_s = where_.map(owned).transpose()?;
+
+        Ok(Self {
+            cbl_ref: CBLFullTextIndexConfiguration {
+                expressionLanguage: query_language as u32,
+                expressions: expressions_s.get_ref(),
+                ignoreAccents: ignore_accents,
+                language: language_s.as_ref().map_or(NULL_SLICE, Slice::get_ref),
+                where_: where_s.as_ref().map_or(NULL_SLICE, Slice::get_ref),
+            },
+            _expressions: expressions_s,
+            _language: language_s,
+            _where: where_s,
+            definition: IndexDefinition::FullText {
+                query_language,
+                expressions: expressions.to_string(),
+                ignore_accents,
+                language: language.map(str::to_string),
+                where_: where_.map(str::to_string),
+            },
         })
     }
 }
 
+#[cfg(feature = "enterprise")]
+enum_from_primitive! {
+    /// Scalar quantizers, by number of bits per dimension.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum ScalarQuantizerType {
+        SQ4 = kCBLSQ4 as isize,
+        SQ6 = kCBLSQ6 as isize,
+        SQ8 = kCBLSQ8 as isize,
+    }
+}
+
+/// The encoding of the vectors in a vector index.
+#[cfg(feature = "enterprise")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VectorEncoding {
+    /// No encoding: 4 bytes per dimension, no data loss.
+    None,
+    /// Scalar quantizer encoding.
+    ScalarQuantizer(ScalarQuantizerType),
+    /// Product quantizer encoding. The number of subquantizers must be greater than 1 and a
+    /// factor of the number of dimensions, the number of bits between 4 and 12.
+    ProductQuantizer { subquantizers: u32, bits: u32 },
+}
+
+#[cfg(feature = "enterprise")]
+impl VectorEncoding {
+    fn create(self) -> *mut CBLVectorEncoding {
+        unsafe {
+            match self {
+                Self::None => CBLVectorEncoding_CreateNone(),
+                Self::ScalarQuantizer(type_) => {
+                    CBLVectorEncoding_CreateScalarQuantizer(type_ as u32)
+                }
+                Self::ProductQuantizer {
+                    subquantizers,
+                    bits,
+                } => CBLVectorEncoding_CreateProductQuantizer(subquantizers, bits),
+            }
+        }
+    }
+
+    fn to_dict(self) -> MutableDict {
+        let mut dict = MutableDict::new();
+        match self {
+            Self::None => dict.at("type").put_string("none"),
+            Self::ScalarQuantizer(type_) => {
+                dict.at("type").put_string("scalarQuantizer");
+                dict.at("bits").put_i64(type_ as i64);
+            }
+            Self::ProductQuantizer {
+                subquantizers,
+                bits,
+            } => {
+                dict.at("type").put_string("productQuantizer");
+                dict.at("subquantizers").put_i64(subquantizers);
+                dict.at("bits").put_i64(bits);
+            }
+        }
+        dict
+    }
+
+    fn from_dict(dict: &Dict) -> Option<Self> {
+        let number = |key: &str| u32::try_from(dict.get(key).as_i64()?).ok();
+        match dict.get("type").as_string()? {
+            "none" => Some(Self::None),
+            "scalarQuantizer" => Some(Self::ScalarQuantizer(ScalarQuantizerType::from_i64(
+                dict.get("bits").as_i64()?,
+            )?)),
+            "productQuantizer" => Some(Self::ProductQuantizer {
+                subquantizers: number("subquantizers")?,
+                bits: number("bits")?,
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "enterprise")]
+enum_from_primitive! {
+    /// The distance metrics of vector indexes.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum DistanceMetric {
+        /// Squared Euclidean distance (AKA Squared L2)
+        EuclideanSquared = kCBLDistanceMetricEuclideanSquared as isize,
+        /// Cosine distance (1.0 - Cosine Similarity)
+        Cosine = kCBLDistanceMetricCosine as isize,
+        /// Euclidean distance (AKA L2)
+        Euclidean = kCBLDistanceMetricEuclidean as isize,
+        /// Dot-product distance (Negative of dot-product)
+        Dot = kCBLDistanceMetricDot as isize,
+    }
+}
+
+/// The optional settings of a vector index.
+#[cfg(feature = "enterprise")]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VectorIndexOptions {
+    /// Whether the index is lazy: it isn't updated when documents change, except when they are
+    /// deleted or purged, and its expression returns the value the vectors are computed from.
+    pub lazy: bool,
+    /// The encoding of the vectors, 8-bit scalar quantizer by default.
+    pub encoding: VectorEncoding,
+    /// The distance metric.
+    pub metric: DistanceMetric,
+    /// The minimum number of vectors for training the index, 0 to let Couchbase Lite decide.
+    pub min_training_size: u32,
+    /// The maximum number of vectors for training the index, 0 to let Couchbase Lite decide.
+    pub max_training_size: u32,
+    /// The number of centroids scanned during a query, 0 to let Couchbase Lite decide.
+    pub num_probes: u32,
+}
+
+#[cfg(feature = "enterprise")]
+impl Default for VectorIndexOptions {
+    fn default() -> Self {
+        unsafe {
+            Self {
+                lazy: kCBLDefaultVectorIndexLazy,
+                encoding: VectorEncoding::ScalarQuantizer(ScalarQuantizerType::SQ8),
+                metric: DistanceMetric::from_u32(kCBLDefaultVectorIndexDistanceMetric)
+                    .unwrap_or(DistanceMetric::EuclideanSquared),
+                min_training_size: kCBLDefaultVectorIndexMinTrainingSize,
+                max_training_size: kCBLDefaultVectorIndexMaxTrainingSize,
+                num_probes: kCBLDefaultVectorIndexNumProbes,
+            }
+        }
+    }
+}
+
+/// ENTERPRISE EDITION ONLY
+///
+/// Vector Index Configuration, for use with the APPROX_VECTOR_DISTANCE() query function.
+#[cfg(feature = "enterprise")]
+#[derive(Debug)]
+pub struct VectorIndexConfiguration {
+    cbl_ref: CBLVectorIndexConfiguration,
+    _expression: Slice<CString>,
+    definition: IndexDefinition,
+}
+
+#[cfg(feature = "enterprise")]
+impl CblRef for VectorIndexConfiguration {
+    type Output = CBLVectorIndexConfiguration;
+    fn get_ref(&self) -> Self::Output {
+        self.cbl_ref
+    }
+}
+
+#[cfg(feature = "enterprise")]
+impl VectorIndexConfiguration {
+    /// Create a Vector Index Configuration.
+    ///   - query_langage:  The language used in the expression (Required).
+    ///   - expression:  The expression returning the vectors, arrays of numbers or Base64
+    ///     strings of little-endian 32-bit floats (Required). For lazy indexes, it returns the
+    ///     value the vectors are computed from.
+    ///   - dimensions:  The number of dimensions of the vectors, at most 4096 (Required).
+    ///   - centroids:  The number of buckets the vectors are partitioned in, at most 64000
+    ///     (Required). The square root of the number of vectors is recommended.
+    ///   - options:  The optional settings, see `VectorIndexOptions`.
+    pub fn new(
+        query_language: QueryLanguage,
+        expression: &str,
+        dimensions: u32,
+        centroids: u32,
+        options: VectorIndexOptions,
+    ) -> Result<Self> {
+        let expression_c = CString::new(expression)
+            .map_err(|_| Error::cbl_error(CouchbaseLiteError::InvalidParameter))?;
+        let expression_s = from_c_str(expression_c, expression.len());
+
+        Ok(Self {
+            cbl_ref: CBLVectorIndexConfiguration {
+                expressionLanguage: query_language as u32,
+                expression: expression_s.get_ref(),
+                dimensions,
+                centroids,
+                isLazy: options.lazy,
+                encoding: options.encoding.create(),
+                metric: options.metric as u32,
+                minTrainingSize: options.min_training_size,
+                maxTrainingSize: options.max_training_size,
+                numProbes: options.num_probes,
+            },
+            _expression: expression_s,
+            definition: IndexDefinition::Vector {
+                query_language,
+                expression: expression.to_string(),
+                dimensions,
+                centroids,
+                options,
+            },
+        })
+    }
+}
+
+#[cfg(feature = "enterprise")]
+impl Drop for VectorIndexConfiguration {
+    fn drop(&mut self) {
+        unsafe { CBLVectorEncoding_Free(self.cbl_ref.encoding) }
+    }
+}
+
+/// The definition of an index created through this crate, see `QueryIndex::definition`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IndexDefinition {
+    /// A value index, see `ValueIndexConfiguration::new`.
+    Value {
+        query_language: QueryLanguage,
+        expressions: String,
+        where_: Option<String>,
+    },
+    /// An array index, see `ArrayIndexConfiguration::new`.
+    Array {
+        query_language: QueryLanguage,
+        path: String,
+        expressions: String,
+    },
+    /// A full-text index, see `FullTextIndexConfiguration::new`.
+    FullText {
+        query_language: QueryLanguage,
+        expressions: String,
+        ignore_accents: bool,
+        language: Option<String>,
+        where_: Option<String>,
+    },
+    /// A vector index, see `VectorIndexConfiguration::new`.
+    #[cfg(feature = "enterprise")]
+    Vector {
+        query_language: QueryLanguage,
+        expression: String,
+        dimensions: u32,
+        centroids: u32,
+        options: VectorIndexOptions,
+    },
+}
+
+impl IndexDefinition {
+    fn create(&self, collection: &Collection, name: &str) -> Result<bool> {
+        match self {
+            Self::Value {
+                query_language,
+                expressions,
+                where_,
+            } => collection.create_index(
+                name,
+                &ValueIndexConfiguration::new(*query_language, expressions, where_.as_deref()),
+            ),
+            Self::Array {
+                query_language,
+                path,
+                expressions,
+            } => collection.create_array_index(
+                name,
+                &ArrayIndexConfiguration::new(*query_language, path, expressions)?,
+            ),
+            Self::FullText {
+                query_language,
+                expressions,
+                ignore_accents,
+                language,
+                where_,
+            } => collection.create_full_text_index(
+                name,
+                &FullTextIndexConfiguration::new(
+                    *query_language,
+                    expressions,
+                    *ignore_accents,
+                    language.as_deref(),
+                    where_.as_deref(),
+                )?,
+            ),
+            #[cfg(feature = "enterprise")]
+            Self::Vector {
+                query_language,
+                expression,
+                dimensions,
+                centroids,
+                options,
+            } => collection.create_vector_index(
+                name,
+                &VectorIndexConfiguration::new(
+                    *query_language,
+                    expression,
+                    *dimensions,
+                    *centroids,
+                    *options,
+                )?,
+            ),
+        }
+    }
+
+    // The stored form of the definition, see `set_definition`.
+    fn to_dict(&self) -> MutableDict {
+        let mut dict = MutableDict::new();
+        match self {
+            Self::Value {
+                query_language,
+                expressions,
+                where_,
+            } => {
+                dict.at("type").put_string("value");
+                dict.at("queryLanguage")
+                    .put_string(query_language_name(*query_language));
+                dict.at("expressions").put_string(expressions);
+                if let Some(where_) = where_ {
+                    dict.at("where").put_string(where_);
+                }
+            }
+            Self::Array {
+                query_language,
+                path,
+                expressions,
+            } => {
+                dict.at("type").put_string("array");
+                dict.at("queryLanguage")
+                    .put_string(query_language_name(*query_language));
+                dict.at("path").put_string(path);
+                dict.at("expressions").put_string(expressions);
+            }
+            Self::FullText {
+                query_language,
+                expressions,
+                ignore_accents,
+                language,
+                where_,
+            } => {
+                dict.at("type").put_string("fullText");
+                dict.at("queryLanguage")
+                    .put_string(query_language_name(*query_language));
+                dict.at("expressions").put_string(expressions);
+                dict.at("ignoreAccents").put_bool(*ignore_accents);
+                if let Some(language) = language {
+                    dict.at("language").put_string(language);
+                }
+                if let Some(where_) = where_ {
+                    dict.at("where").put_string(where_);
+                }
+            }
+            #[cfg(feature = "enterprise")]
+            Self::Vector {
+                query_language,
+                expression,
+                dimensions,
+                centroids,
+                options,
+            } => {
+                dict.at("type").put_string("vector");
+                dict.at("queryLanguage")
+                    .put_string(query_language_name(*query_language));
+                dict.at("expression").put_string(expression);
+                dict.at("dimensions").put_i64(*dimensions);
+                dict.at("centroids").put_i64(*centroids);
+                dict.at("lazy").put_bool(options.lazy);
+                dict.at("encoding").put_value(&options.encoding.to_dict());
+                dict.at("metric").put_i64(options.metric as i64);
+                dict.at("minTrainingSize")
+                    .put_i64(options.min_training_size);
+                dict.at("maxTrainingSize")
+                    .put_i64(options.max_training_size);
+                dict.at("numProbes").put_i64(options.num_probes);
+            }
+        }
+        dict
+    }
+
+    // Reads the stored form of a definition, or returns None if it isn't one.
+    fn from_dict(dict: &Dict) -> Option<Self> {
+        let string = |key: &str| dict.get(key).as_string().map(str::to_string);
+        let query_language = match dict.get("queryLanguage").as_string()? {
+            "JSON" => QueryLanguage::JSON,
+            "N1QL" => QueryLanguage::N1QL,
+            _ => return None,
+        };
+        match dict.get("type").as_string()? {
+            "value" => Some(Self::Value {
+                query_language,
+                expressions: string("expressions")?,
+                where_: string("where"),
+            }),
+            "array" => Some(Self::Array {
+                query_language,
+                path: string("path")?,
+                expressions: string("expressions")?,
+            }),
+            "fullText" => Some(Self::FullText {
+                query_language,
+                expressions: string("expressions")?,
+                ignore_accents: dict.get("ignoreAccents").as_bool()?,
+                language: string("language"),
+                where_: string("where"),
+            }),
+            #[cfg(feature = "enterprise")]
+            "vector" => {
+                let number = |key: &str| u32::try_from(dict.get(key).as_i64()?).ok();
+                Some(Self::Vector {
+                    query_language,
+                    expression: string("expression")?,
+                    dimensions: number("dimensions")?,
+                    centroids: number("centroids")?,
+                    options: VectorIndexOptions {
+                        lazy: dict.get("lazy").as_bool()?,
+                        encoding: VectorEncoding::from_dict(&dict.get("encoding").as_dict())?,
+                        metric: DistanceMetric::from_i64(dict.get("metric").as_i64()?)?,
+                        min_training_size: number("minTrainingSize")?,
+                        max_training_size: number("maxTrainingSize")?,
+                        num_probes: number("numProbes")?,
+                    },
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+const fn query_language_name(query_language: QueryLanguage) -> &'static str {
+    match query_language {
+        QueryLanguage::JSON => "JSON",
+        QueryLanguage::N1QL => "N1QL",
+    }
+}
+
+//////// STORED DEFINITIONS:
+
+// Couchbase Lite C can't read the definition of an index back, so the definitions of the indexes
+// created through this crate are stored in a collection of their own, in a document per indexed
+// collection mapping the names of its indexes to their definitions.
+const DEFINITIONS_SCOPE: &str = "couchbase_lite_rs";
+const DEFINITIONS_COLLECTION: &str = "index_definitions";
+
+// Document IDs can't start with '_', unlike the name of the default scope.
+fn definitions_document_id(collection_full_name: &str) -> String {
+    format!("collection:{collection_full_name}")
+}
+
+fn definitions_collection(database: &Database, create: bool) -> Result<Option<Collection>> {
+    if create {
+        database
+            .create_collection(
+                DEFINITIONS_COLLECTION.to_string(),
+                DEFINITIONS_SCOPE.to_string(),
+            )
+            .map(Some)
+    } else {
+        database.collection(
+            DEFINITIONS_COLLECTION.to_string(),
+            DEFINITIONS_SCOPE.to_string(),
+        )
+    }
+}
+
+fn definition(collection: &Collection, name: &str) -> Result<Option<IndexDefinition>> {
+    let Some(definitions) = definitions_collection(&collection.database(), false)? else {
+        return Ok(None);
+    };
+    let document = not_found_as_none(
+        definitions.get_document(&definitions_document_id(&collection.full_name())),
+    )?;
+    Ok(document.and_then(|document| {
+        IndexDefinition::from_dict(&document.properties().get(name).as_dict())
+    }))
+}
+
+fn set_definition(
+    collection: &Collection,
+    name: &str,
+    definition: Option<&IndexDefinition>,
+) -> Result<()> {
+    let Some(mut definitions) =
+        definitions_collection(&collection.database(), definition.is_some())?
+    else {
+        return Ok(());
+    };
+    let options = UpdateOptions {
+        create_missing: definition.is_some(),
+        ..UpdateOptions::default()
+    };
+    let result = definitions.update_with(
+        &definitions_document_id(&collection.full_name()),
+        &options,
+        |properties| {
+            match definition {
+                Some(definition) => properties.at(name).put_value(&definition.to_dict()),
+                None if properties.get(name).get_type() == ValueType::Undefined => {
+                    return Ok(Update::Abort);
+                }
+                None => properties.remove(name),
+            }
+            Ok(Update::Save)
+        },
+    );
+    not_found_as_none(result).map(|_| ())
+}
+
+// Forgets the indexes of a deleted collection.
+pub(crate) fn forget_collection(database: &Database, full_name: &str) -> Result<()> {
+    if full_name == format!("{DEFINITIONS_SCOPE}.{DEFINITIONS_COLLECTION}") {
+        return Ok(());
+    }
+    let Some(mut definitions) = definitions_collection(database, false)? else {
+        return Ok(());
+    };
+    not_found_as_none(definitions.purge_document_by_id(&definitions_document_id(full_name)))
+        .map(|_| ())
+}
+
+/// The declaration of an index, for `Collection::sync_indexes`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexSpec {
+    name: String,
+    definition: IndexDefinition,
+}
+
+impl IndexSpec {
+    /// Declares a value index, see `ValueIndexConfiguration::new`.
+    pub fn value(name: &str, query_language: QueryLanguage, expressions: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            definition: IndexDefinition::Value {
+                query_language,
+                expressions: expressions.to_string(),
+                where_: None,
+            },
+        }
+    }
+
+    /// Declares an array index, see `ArrayIndexConfiguration::new`.
+    pub fn array(name: &str, query_language: QueryLanguage, path: &str, expressions: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            definition: IndexDefinition::Array {
+                query_language,
+                path: path.to_string(),
+                expressions: expressions.to_string(),
+            },
+        }
+    }
+
+    /// Declares a full-text index, see `FullTextIndexConfiguration::new`.
+    pub fn full_text(
+        name: &str,
+        query_language: QueryLanguage,
+        expressions: &str,
+        ignore_accents: bool,
+        language: Option<&str>,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            definition: IndexDefinition::FullText {
+                query_language,
+                expressions: expressions.to_string(),
+                ignore_accents,
+                language: language.map(str::to_string),
+                where_: None,
+            },
+        }
+    }
+
+    /// ENTERPRISE EDITION ONLY
+    ///
+    /// Declares a vector index, see `VectorIndexConfiguration::new`.
+    #[cfg(feature = "enterprise")]
+    pub fn vector(
+        name: &str,
+        query_language: QueryLanguage,
+        expression: &str,
+        dimensions: u32,
+        centroids: u32,
+        options: VectorIndexOptions,
+    ) -> Self {
+        Self {
+            name: name.to_string(),
+            definition: IndexDefinition::Vector {
+                query_language,
+                expression: expression.to_string(),
+                dimensions,
+                centroids,
+                options,
+            },
+        }
+    }
+
+    /// Only indexes the documents matching the condition.
+    /// Array and vector indexes have no condition: this fails with `InvalidParameter` on them.
+    pub fn where_(mut self, condition: &str) -> Result<Self> {
+        let (IndexDefinition::Value { where_, .. } | IndexDefinition::FullText { where_, .. }) =
+            &mut self.definition
+        else {
+            return Err(Error::cbl_error(CouchbaseLiteError::InvalidParameter));
+        };
+        *where_ = Some(condition.to_string());
+        Ok(self)
+    }
+
+    /// Returns the index's name.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the definition of the index.
+    pub const fn definition(&self) -> &IndexDefinition {
+        &self.definition
+    }
+}
+
+/// What `Collection::sync_indexes` did, with the names of the indexes.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct IndexSyncReport {
+    /// Declared indexes that didn't exist.
+    pub created: Vec<String>,
+    /// Declared indexes that already existed with another definition, and were re-created.
+    pub updated: Vec<String>,
+    /// Declared indexes that already existed with the same definition, and were left untouched.
+    pub unchanged: Vec<String>,
+    /// Declared indexes that already existed with an unknown definition, see
+    /// `QueryIndex::definition`, and were left untouched.
+    pub unverified: Vec<String>,
+    /// Existing indexes that weren't declared, and were deleted.
+    pub deleted: Vec<String>,
+    /// Existing indexes that weren't declared, and were kept.
+    pub unknown: Vec<String>,
+}
+
 /// QueryIndex represents an existing index in a collection.
 /// The QueryIndex can be used to obtain
 /// a IndexUpdater object for updating the vector index in lazy mode.
@@ -179,6 +902,15 @@ impl QueryIndex {
     pub fn collection(&self) -> Collection {
         unsafe { Collection::reference(CBLQueryIndex_Collection(self.get_ref())) }
     }
+
+    /// Returns the definition of the index, or None if it is unknown.
+    /// Couchbase Lite C can't read the definition of an index back, so the indexes created
+    /// through this crate store their definition in the `couchbase_lite_rs.index_definitions`
+    /// collection. The definitions of the indexes created otherwise, for instance by another
+    /// Couchbase Lite binding, are unknown.
+    pub fn definition(&self) -> Result<Option<IndexDefinition>> {
+        definition(&self.collection(), &self.name())
+    }
 }
 
 impl Drop for QueryIndex {
@@ -205,6 +937,12 @@ impl Database {
             )
         };
         if !err {
+            query_cache::schema_changed(self);
+            set_definition(
+                &self.default_collection_or_error()?,
+                name,
+                Some(&config.definition),
+            )?;
             return Ok(r);
         }
         failure(err)
@@ -217,11 +955,23 @@ impl Database {
         let slice = from_str(name);
         let r = unsafe { CBLDatabase_DeleteIndex(self.get_ref(), slice.get_ref(), &mut err) };
         if !err {
+            query_cache::schema_changed(self);
+            set_definition(&self.default_collection_or_error()?, name, None)?;
             return Ok(r);
         }
         failure(err)
     }
 
+    /// ENTERPRISE EDITION ONLY
+    ///
+    /// Enables the Vector Search extension, given the directory containing its library.
+    /// It must be called before opening the databases using vector indexes.
+    #[cfg(feature = "enterprise")]
+    pub fn enable_vector_search<P: AsRef<Path>>(path: P) -> Result<()> {
+        let path = from_str(path.as_ref().to_str().unwrap_or_default());
+        unsafe { check_bool(|error| CBL_EnableVectorSearch(path.get_ref(), error)) }
+    }
+
     /// Returns the names of the indexes on this database, as an Array of strings.
     #[deprecated(note = "please use `get_index_names` on default collection instead")]
     pub fn get_index_names(&self) -> Array {
@@ -246,6 +996,8 @@ impl Collection {
             )
         };
         if !err {
+            query_cache::schema_changed(&self.database());
+            set_definition(self, name, Some(&config.definition))?;
             return Ok(r);
         }
         failure(err)
@@ -266,6 +1018,66 @@ impl Collection {
             )
         };
         if !err {
+            query_cache::schema_changed(&self.database());
+            set_definition(self, name, Some(&config.definition))?;
+            return Ok(r);
+        }
+        failure(err)
+    }
+
+    /// Creates a full-text index in the collection, for use with the MATCH() and RANK()
+    /// query functions. If an identical index with that name already exists, nothing happens
+    /// (and no error is returned.) If a non-identical index with that name already exists,
+    /// it is deleted and re-created.
+    pub fn create_full_text_index(
+        &self,
+        name: &str,
+        config: &FullTextIndexConfiguration,
+    ) -> Result<bool> {
+        let mut err = CBLError::default();
+        let slice = from_str(name);
+        let r = unsafe {
+            CBLCollection_CreateFullTextIndex(
+                self.get_ref(),
+                slice.get_ref(),
+                config.get_ref(),
+                &mut err,
+            )
+        };
+        if !err {
+            query_cache::schema_changed(&self.database());
+            set_definition(self, name, Some(&config.definition))?;
+            return Ok(r);
+        }
+        failure(err)
+    }
+
+    /// ENTERPRISE EDITION ONLY
+    ///
+    /// Creates a vector index in the collection, for use with the APPROX_VECTOR_DISTANCE()
+    /// query function. Vector search must be enabled with `Database::enable_vector_search`
+    /// before the database is opened. If an identical index with that name already exists,
+    /// nothing happens (and no error is returned.) If a non-identical index with that name
+    /// already exists, it is deleted and re-created.
+    #[cfg(feature = "enterprise")]
+    pub fn create_vector_index(
+        &self,
+        name: &str,
+        config: &VectorIndexConfiguration,
+    ) -> Result<bool> {
+        let mut err = CBLError::default();
+        let slice = from_str(name);
+        let r = unsafe {
+            CBLCollection_CreateVectorIndex(
+                self.get_ref(),
+                slice.get_ref(),
+                config.get_ref(),
+                &mut err,
+            )
+        };
+        if !err {
+            query_cache::schema_changed(&self.database());
+            set_definition(self, name, Some(&config.definition))?;
             return Ok(r);
         }
         failure(err)
@@ -277,6 +1089,8 @@ impl Collection {
         let slice = from_str(name);
         let r = unsafe { CBLCollection_DeleteIndex(self.get_ref(), slice.get_ref(), &mut err) };
         if !err {
+            query_cache::schema_changed(&self.database());
+            set_definition(self, name, None)?;
             return Ok(r);
         }
         failure(err)
@@ -289,6 +1103,63 @@ impl Collection {
         check_error(&err).map(|()| Array::wrap(arr))
     }
 
+    /// Returns the names of the indexes in the collection.
+    pub fn index_names(&self) -> Result<Vec<String>> {
+        Ok(self
+            .get_index_names()?
+            .iter()
+            .filter_map(|name| name.as_string().map(str::to_string))
+            .collect())
+    }
+
+    /// Makes the indexes of the collection match their declarations: creates the missing
+    /// indexes and re-creates the ones whose definition changed. The indexes whose definition
+    /// isn't known, see `QueryIndex::definition`, are left untouched. Indexes that aren't
+    /// declared are deleted with `delete_unknown`, and kept otherwise.
+    /// Declaring two indexes with the same name fails with `InvalidParameter`.
+    pub fn sync_indexes(
+        &self,
+        specs: &[IndexSpec],
+        delete_unknown: bool,
+    ) -> Result<IndexSyncReport> {
+        let names: HashSet<&str> = specs.iter().map(IndexSpec::name).collect();
+        if names.len() != specs.len() {
+            return Err(Error::cbl_error(CouchbaseLiteError::InvalidParameter));
+        }
+
+        let existing = self.index_names()?;
+        let mut report = IndexSyncReport::default();
+        for name in existing
+            .iter()
+            .filter(|name| !names.contains(name.as_str()))
+        {
+            if delete_unknown {
+                self.delete_index(name)?;
+                report.deleted.push(name.clone());
+            } else {
+                report.unknown.push(name.clone());
+            }
+        }
+        for spec in specs {
+            if !existing.iter().any(|name| name == spec.name()) {
+                spec.definition.create(self, &spec.name)?;
+                report.created.push(spec.name.clone());
+            } else {
+                match definition(self, &spec.name)? {
+                    None => report.unverified.push(spec.name.clone()),
+                    Some(definition) if definition == spec.definition => {
+                        report.unchanged.push(spec.name.clone());
+                    }
+                    Some(_) => {
+                        spec.definition.create(self, &spec.name)?;
+                        report.updated.push(spec.name.clone());
+                    }
+                }
+            }
+        }
+        Ok(report)
+    }
+
     /// Returns the names of the indexes in the collection, as a Fleece array of strings.
     pub fn get_index(&self, name: &str) -> Result<QueryIndex> {
         let mut err = CBLError::default();
@@ -0,0 +1,476 @@
+//! Suggests indexes for queries that scan a whole collection or sort their results in a
+//! temporary B-tree, from their query plans.
+//!
+//! The suggestions are derived from the properties the queries filter and sort on: equality
+//! tests first, then range tests, then the sort order. They are a starting point, check the
+//! effect of an index with `Query::plan`.
+
+use crate::{
+    CouchbaseLiteError, Database, Error, Query, QueryLanguage, Result,
+    index::{ArrayIndexConfiguration, ValueIndexConfiguration},
+    query_plan::StepKind,
+};
+use serde_json::Value as Json;
+use std::collections::HashMap;
+use std::fmt;
+
+/** Collects the queries of an application, to suggest the indexes they need. */
+#[derive(Default)]
+pub struct IndexAdvisor {
+    queries: Vec<(String, Query)>,
+}
+
+impl IndexAdvisor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /** Registers a query, with a name identifying it in the suggestions. */
+    pub fn add_query(&mut self, name: &str, query: Query) -> &mut Self {
+        self.queries.push((name.to_string(), query));
+        self
+    }
+
+    /** Returns the indexes suggested for the registered queries. A suggestion shared by
+    several queries is returned once. Suggestions whose index name already exists in the
+    collection are skipped: the names are derived from the indexed expressions, so the index
+    has already been created. */
+    pub fn advise(&self, db: &Database) -> Result<Vec<IndexSuggestion>> {
+        let mut suggestions: Vec<IndexSuggestion> = Vec::new();
+        for (name, query) in &self.queries {
+            for suggestion in suggest(name, query)? {
+                match suggestions.iter_mut().find(|s| {
+                    s.scope == suggestion.scope
+                        && s.collection == suggestion.collection
+                        && s.index == suggestion.index
+                }) {
+                    Some(existing) => existing.queries.extend(suggestion.queries),
+                    None => suggestions.push(suggestion),
+                }
+            }
+        }
+
+        let mut existing_names = HashMap::new();
+        let mut advised = Vec::new();
+        for suggestion in suggestions {
+            let key = (suggestion.scope.clone(), suggestion.collection.clone());
+            if !existing_names.contains_key(&key) {
+                let names: Vec<String> =
+                    match db.collection(suggestion.collection.clone(), suggestion.scope.clone())? {
+                        Some(collection) => collection
+                            .get_index_names()?
+                            .iter()
+                            .filter_map(|name| name.as_string().map(str::to_string))
+                            .collect(),
+                        None => Vec::new(),
+                    };
+                existing_names.insert(key.clone(), names);
+            }
+            if !existing_names[&key].contains(&suggestion.name) {
+                advised.push(suggestion);
+            }
+        }
+        Ok(advised)
+    }
+
+    /** Creates the suggested indexes, returning the suggestions that were applied. */
+    pub fn create_indexes(&self, db: &Database) -> Result<Vec<IndexSuggestion>> {
+        let suggestions = self.advise(db)?;
+        for suggestion in &suggestions {
+            suggestion.create(db)?;
+        }
+        Ok(suggestions)
+    }
+}
+
+/** Why an index is suggested. */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexReason {
+    /** The query reads every document of the collection. */
+    FullScan,
+    /** The query sorts its results in a temporary B-tree. */
+    TempBTree,
+}
+
+/** The definition of a suggested index. Expressions are in N1QL syntax. */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SuggestedIndex {
+    /** A value index, see `ValueIndexConfiguration`. */
+    Value { expressions: Vec<String> },
+    /** An array index for `UNNEST` queries, see `ArrayIndexConfiguration`. No expression means
+    the array holds scalar values. */
+    Array {
+        path: String,
+        expressions: Vec<String>,
+    },
+}
+
+/** An index suggested by the `IndexAdvisor`. */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IndexSuggestion {
+    /** The name of the index, derived from its expressions. */
+    pub name: String,
+    pub scope: String,
+    pub collection: String,
+    pub index: SuggestedIndex,
+    pub reason: IndexReason,
+    /** The names of the queries the index is suggested for. */
+    pub queries: Vec<String>,
+}
+
+impl IndexSuggestion {
+    /** Returns the indexed expressions as a comma-separated N1QL list, as expected by
+    `ValueIndexConfiguration::new` and `ArrayIndexConfiguration::new`. */
+    pub fn expressions(&self) -> String {
+        match &self.index {
+            SuggestedIndex::Value { expressions } | SuggestedIndex::Array { expressions, .. } => {
+                expressions.join(", ")
+            }
+        }
+    }
+
+    /** Returns the configuration of an array index suggestion. */
+    pub fn array_configuration(&self) -> Option<Result<ArrayIndexConfiguration>> {
+        match &self.index {
+            SuggestedIndex::Value { .. } => None,
+            SuggestedIndex::Array { path, .. } => Some(ArrayIndexConfiguration::new(
+                QueryLanguage::N1QL,
+                path,
+                &self.expressions(),
+            )),
+        }
+    }
+
+    /** Creates the index in its collection. */
+    pub fn create(&self, db: &Database) -> Result<bool> {
+        let collection = db
+            .collection(self.collection.clone(), self.scope.clone())?
+            .ok_or_else(|| Error::cbl_error(CouchbaseLiteError::NotFound))?;
+        match self.array_configuration() {
+            Some(config) => collection.create_array_index(&self.name, &config?),
+            None => {
+                let expressions = self.expressions();
+                collection.create_index(
+                    &self.name,
+                    &ValueIndexConfiguration::new(QueryLanguage::N1QL, &expressions, None),
+                )
+            }
+        }
+    }
+}
+
+impl fmt::Display for IndexSuggestion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} ON {}.{}", self.name, self.scope, self.collection)?;
+        if let SuggestedIndex::Array { path, .. } = &self.index {
+            write!(f, " UNNEST {path}")?;
+        }
+        write!(f, " ({})", self.expressions())
+    }
+}
+
+//////// QUERY ANALYSIS:
+
+// A property path, relative to the collection or to an `UNNEST` alias.
+#[derive(PartialEq)]
+enum Target {
+    Collection,
+    Unnest(String),
+}
+
+// The data sources of a query, from its JSON form.
+struct Sources {
+    scope: String,
+    collection: String,
+    aliases: Vec<String>,
+    // The unnest aliases with their array path, in N1QL index syntax like `contacts[].phones`.
+    unnests: Vec<(String, String)>,
+}
+
+impl Sources {
+    fn parse(from: Option<&Json>) -> Self {
+        let mut sources = Self {
+            scope: "_default".to_string(),
+            collection: "_default".to_string(),
+            aliases: vec!["_".to_string(), "_default".to_string()],
+            unnests: Vec::new(),
+        };
+        let Some(from) = from.and_then(Json::as_array) else {
+            return sources;
+        };
+        for source in from {
+            let alias = source.get("AS").and_then(Json::as_str).map(str::to_string);
+            if let Some(unnest) = source.get("UNNEST") {
+                if let (Some(alias), Some((target, path))) = (alias, sources.property(unnest)) {
+                    let path = match target {
+                        Target::Collection => path.join("."),
+                        Target::Unnest(parent) => {
+                            let parent = sources.unnest_path(&parent).unwrap_or_default();
+                            std::iter::once(format!("{parent}[]"))
+                                .chain(path)
+                                .collect::<Vec<_>>()
+                                .join(".")
+                        }
+                    };
+                    sources.unnests.push((alias, path));
+                }
+            } else if source.get("JOIN").is_none() && source.get("ON").is_none() {
+                if let Some(collection) = source.get("COLLECTION").and_then(Json::as_str) {
+                    match (
+                        source.get("SCOPE").and_then(Json::as_str),
+                        collection.split_once('.'),
+                    ) {
+                        (Some(scope), _) => {
+                            sources.scope = scope.to_string();
+                            sources.collection = collection.to_string();
+                        }
+                        (None, Some((scope, collection))) => {
+                            sources.scope = scope.to_string();
+                            sources.collection = collection.to_string();
+                        }
+                        (None, None) => sources.collection = collection.to_string(),
+                    }
+                    if sources.collection == "_" {
+                        sources.collection = "_default".to_string();
+                    }
+                    sources.aliases.push(collection.to_string());
+                }
+                sources.aliases.extend(alias);
+            }
+        }
+        sources
+    }
+
+    fn unnest_path(&self, alias: &str) -> Option<String> {
+        self.unnests
+            .iter()
+            .find(|(unnest, _)| unnest == alias)
+            .map(|(_, path)| path.clone())
+    }
+
+    // Resolves a property expression like `[".alias.a.b"]` or `[".", "a", "b"]`.
+    // Document metadata, like `meta().id`, is not indexable.
+    fn property(&self, expression: &Json) -> Option<(Target, Vec<String>)> {
+        let items = expression.as_array()?;
+        let first = items.first()?.as_str()?.strip_prefix('.')?;
+        let mut path: Vec<String> = if first.is_empty() {
+            items[1..]
+                .iter()
+                .map(|item| item.as_str().map(str::to_string))
+                .collect::<Option<_>>()?
+        } else {
+            if items.len() > 1 {
+                return None;
+            }
+            first.split('.').map(str::to_string).collect()
+        };
+
+        let head = path.first()?.clone();
+        let target = if self.unnests.iter().any(|(alias, _)| *alias == head) {
+            path.remove(0);
+            Target::Unnest(head)
+        } else {
+            if self.aliases.contains(&head) && path.len() > 1 {
+                path.remove(0);
+            }
+            if path[0].starts_with('_') {
+                return None;
+            }
+            Target::Collection
+        };
+        Some((target, path))
+    }
+}
+
+// The properties tested by a query, in index order.
+#[derive(Default)]
+struct Tested {
+    equalities: Vec<Vec<String>>,
+    ranges: Vec<Vec<String>>,
+}
+
+impl Tested {
+    fn expressions(&self, order: &[Vec<String>]) -> Vec<String> {
+        let mut expressions: Vec<String> = Vec::new();
+        for path in self.equalities.iter().chain(&self.ranges).chain(order) {
+            let expression = n1ql_path(path);
+            if !expressions.contains(&expression) {
+                expressions.push(expression);
+            }
+        }
+        expressions
+    }
+}
+
+fn suggest(name: &str, query: &Query) -> Result<Vec<IndexSuggestion>> {
+    let plan = query.plan()?;
+    let reason = if plan.has_full_scan() {
+        IndexReason::FullScan
+    } else if plan
+        .steps
+        .iter()
+        .any(|step| step.kind == StepKind::TempBTree)
+    {
+        IndexReason::TempBTree
+    } else {
+        return Ok(Vec::new());
+    };
+    let json: Json = serde_json::from_str(&plan.json)
+        .map_err(|_| Error::cbl_error(CouchbaseLiteError::InvalidQuery))?;
+    let sources = Sources::parse(json.get("FROM"));
+
+    let mut tested: Vec<(Target, Tested)> = Vec::new();
+    if let Some(condition) = json.get("WHERE") {
+        collect_tests(&sources, condition, &mut tested);
+    }
+    let order: Vec<Vec<String>> = json
+        .get("ORDER_BY")
+        .and_then(Json::as_array)
+        .into_iter()
+        .flatten()
+        .filter_map(|ordering| {
+            let expression = match ordering.as_array().map(Vec::as_slice) {
+                Some([direction, expression])
+                    if matches!(direction.as_str(), Some("ASC" | "DESC")) =>
+                {
+                    expression
+                }
+                _ => ordering,
+            };
+            match sources.property(expression) {
+                Some((Target::Collection, path)) => Some(path),
+                _ => None,
+            }
+        })
+        .collect();
+
+    let suggestion = |index: SuggestedIndex| IndexSuggestion {
+        name: index_name(&index),
+        scope: sources.scope.clone(),
+        collection: sources.collection.clone(),
+        index,
+        reason,
+        queries: vec![name.to_string()],
+    };
+
+    let mut suggestions = Vec::new();
+    let collection_tests = tested
+        .iter()
+        .find(|(target, _)| *target == Target::Collection)
+        .map(|(_, tests)| tests);
+    let expressions = collection_tests.map_or_else(
+        || Tested::default().expressions(&order),
+        |tests| tests.expressions(&order),
+    );
+    if !expressions.is_empty() {
+        suggestions.push(suggestion(SuggestedIndex::Value { expressions }));
+    }
+    if reason == IndexReason::FullScan {
+        for (target, tests) in &tested {
+            let Target::Unnest(alias) = target else {
+                continue;
+            };
+            let Some(path) = sources.unnest_path(alias) else {
+                continue;
+            };
+            // Tests of the array values themselves have an empty path.
+            let expressions: Vec<String> = tests
+                .expressions(&[])
+                .into_iter()
+                .filter(|expression| !expression.is_empty())
+                .collect();
+            suggestions.push(suggestion(SuggestedIndex::Array { path, expressions }));
+        }
+    }
+    Ok(suggestions)
+}
+
+// Collects the properties compared to a value in the `AND`ed terms of a condition. Terms of an
+// `OR` or `NOT` can't all use the same index, so they are ignored.
+fn collect_tests(sources: &Sources, condition: &Json, tested: &mut Vec<(Target, Tested)>) {
+    let Some([op, operands @ ..]) = condition.as_array().map(Vec::as_slice) else {
+        return;
+    };
+    let Some(op) = op.as_str() else {
+        return;
+    };
+    if op == "AND" {
+        for operand in operands {
+            collect_tests(sources, operand, tested);
+        }
+        return;
+    }
+
+    let equality = match op {
+        "=" | "==" | "IN" | "IS" => true,
+        "<" | "<=" | ">" | ">=" | "BETWEEN" => false,
+        // Only a pattern with a fixed prefix can use an index.
+        "LIKE" => match operands.get(1).and_then(Json::as_str) {
+            Some(pattern) if !pattern.starts_with(['%', '_']) => false,
+            _ => return,
+        },
+        _ => return,
+    };
+    let property = match operands {
+        [a, b, ..] => match (sources.property(a), sources.property(b)) {
+            (Some(property), None) => property,
+            (None, Some(property)) if matches!(op, "=" | "==" | "<" | "<=" | ">" | ">=") => {
+                property
+            }
+            _ => return,
+        },
+        _ => return,
+    };
+
+    let (target, path) = property;
+    let index = match tested.iter().position(|(t, _)| *t == target) {
+        Some(index) => index,
+        None => {
+            tested.push((target, Tested::default()));
+            tested.len() - 1
+        }
+    };
+    let tests = &mut tested[index].1;
+    if equality {
+        tests.equalities.push(path);
+    } else {
+        tests.ranges.push(path);
+    }
+}
+
+// Formats a property path in N1QL, quoting the names that aren't plain identifiers.
+fn n1ql_path(path: &[String]) -> String {
+    path.iter()
+        .map(|name| {
+            let plain = name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+                && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if plain {
+                name.clone()
+            } else {
+                format!("`{}`", name.replace('`', "``"))
+            }
+        })
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+// Derives the name of an index from its path and expressions, like `idx_contacts_phones_type`.
+fn index_name(index: &SuggestedIndex) -> String {
+    let parts = match index {
+        SuggestedIndex::Value { expressions } => expressions.iter().collect::<Vec<_>>(),
+        SuggestedIndex::Array { path, expressions } => {
+            std::iter::once(path).chain(expressions).collect()
+        }
+    };
+    let mut name = "idx".to_string();
+    for part in parts {
+        for word in part
+            .split(|c: char| !c.is_ascii_alphanumeric())
+            .filter(|word| !word.is_empty())
+        {
+            name.push('_');
+            name.push_str(word);
+        }
+    }
+    name
+}
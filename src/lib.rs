@@ -43,9 +43,19 @@ pub mod encryptable;
 pub mod error;
 pub mod fleece;
 pub mod fleece_mutable;
+pub mod full_text;
 pub mod index;
+pub mod index_advisor;
 pub mod logging;
+pub mod paginator;
 pub mod query;
+#[cfg(feature = "arrow")]
+pub mod query_arrow;
+pub mod query_builder;
+pub mod query_cache;
+pub mod query_export;
+pub mod query_plan;
+pub mod query_profiler;
 pub mod replicator;
 pub mod scope;
 pub mod slice;
@@ -54,14 +64,26 @@ mod c_api;
 
 use self::c_api::{
     CBLListenerToken, CBLRefCounted, CBL_DumpInstances, CBL_InstanceCount, CBL_Release, CBL_Retain,
-    CBLListener_Remove, CBL_Now, CBLITE_VERSION,
+    CBLListener_Remove, CBL_Now, CBLITE_VERSION, FLTimestamp_FromString, FLTimestamp_ToString,
 };
 #[cfg(target_os = "android")]
 use self::c_api::{CBLError, CBLInitContext, CBL_Init};
-use std::{ffi::CStr, time::Duration};
+use self::slice::from_str;
+use std::{
+    ffi::CStr,
+    fmt,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
 
 //////// RE-EXPORT:
 
+#[doc(hidden)]
+pub mod __private {
+    // Used by the `params!` macro
+    pub use serde_json;
+}
+
 pub use blob::*;
 pub use database::*;
 pub use document::*;
@@ -78,12 +100,15 @@ pub trait CblRef {
     fn get_ref(&self) -> Self::Output;
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
 /// A time value for document expiration. Defined as milliseconds since the Unix epoch (1/1/1970.)
 pub struct Timestamp {
     pub(crate) timestamp: i64,
 }
 
+/// Returned by Fleece when a date-time can't be parsed (`FLTimestampNone`.)
+pub(crate) const TIMESTAMP_NONE: i64 = i64::MIN;
+
 impl Timestamp {
     pub fn now() -> Timestamp {
         Timestamp {
@@ -92,12 +117,38 @@ impl Timestamp {
     }
 
     /// Create a Timestamp from milliseconds since the Unix epoch (1/1/1970.)
-    pub(crate) fn new(milliseconds_from_epoch: i64) -> Self {
+    pub fn new(milliseconds_from_epoch: i64) -> Self {
         Timestamp {
             timestamp: milliseconds_from_epoch,
         }
     }
 
+    /// Returns the number of milliseconds since the Unix epoch (1/1/1970.)
+    pub const fn as_millis(&self) -> i64 {
+        self.timestamp
+    }
+
+    /// Parses an ISO-8601 date-time string, the same way the SQL++ function `STR_TO_MILLIS` does.
+    /// Returns None if the string is not a valid date-time.
+    pub fn parse_iso8601(date_time: &str) -> Option<Self> {
+        let timestamp = unsafe { FLTimestamp_FromString(from_str(date_time).get_ref()) };
+        if timestamp == TIMESTAMP_NONE {
+            return None;
+        }
+        Some(Self::new(timestamp))
+    }
+
+    /// Formats the timestamp as an ISO-8601 date-time string, like "2008-08-07T05:18:51.589Z".
+    /// In UTC this is the form produced by the SQL++ function `MILLIS_TO_UTC`, otherwise the
+    /// local time form produced by `MILLIS_TO_STR`.
+    pub fn to_iso8601(&self, as_utc: bool) -> String {
+        unsafe {
+            FLTimestamp_ToString(self.timestamp, as_utc)
+                .to_string()
+                .unwrap_or_default()
+        }
+    }
+
     pub fn add(&self, duration: Duration) -> Self {
         Timestamp {
             timestamp: self.timestamp + duration.as_millis() as i64,
@@ -105,6 +156,124 @@ impl Timestamp {
     }
 }
 
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.to_iso8601(true))
+    }
+}
+
+impl FromStr for Timestamp {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse_iso8601(s).ok_or(Error::cbl_error(CouchbaseLiteError::InvalidParameter))
+    }
+}
+
+/// Times are rounded down to the millisecond, also before the epoch: 1.5 ms before the epoch is
+/// -2 ms, not -1 ms.
+impl From<SystemTime> for Timestamp {
+    fn from(time: SystemTime) -> Self {
+        match time.duration_since(UNIX_EPOCH) {
+            Ok(after) => Self::new(after.as_millis() as i64),
+            Err(before) => {
+                let before = before.duration();
+                let partial = u128::from(before.subsec_nanos() % 1_000_000 != 0);
+                Self::new(-((before.as_millis() + partial) as i64))
+            }
+        }
+    }
+}
+
+impl From<Timestamp> for SystemTime {
+    fn from(timestamp: Timestamp) -> Self {
+        let millis = Duration::from_millis(timestamp.timestamp.unsigned_abs());
+        if timestamp.timestamp >= 0 {
+            UNIX_EPOCH + millis
+        } else {
+            UNIX_EPOCH - millis
+        }
+    }
+}
+
+/// Serialized as an ISO-8601 UTC string, like `Slot::put_timestamp`.
+impl serde::Serialize for Timestamp {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Deserialized from an ISO-8601 string, or from a number of milliseconds since the Unix epoch.
+impl<'de> serde::Deserialize<'de> for Timestamp {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        struct Visitor;
+
+        impl serde::de::Visitor<'_> for Visitor {
+            type Value = Timestamp;
+
+            fn expecting(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                f.write_str("an ISO-8601 date-time string or milliseconds since the Unix epoch")
+            }
+
+            fn visit_i64<E: serde::de::Error>(self, v: i64) -> std::result::Result<Timestamp, E> {
+                Ok(Timestamp::new(v))
+            }
+
+            fn visit_u64<E: serde::de::Error>(self, v: u64) -> std::result::Result<Timestamp, E> {
+                i64::try_from(v)
+                    .map(Timestamp::new)
+                    .map_err(|_| E::invalid_value(serde::de::Unexpected::Unsigned(v), &self))
+            }
+
+            fn visit_str<E: serde::de::Error>(self, v: &str) -> std::result::Result<Timestamp, E> {
+                Timestamp::parse_iso8601(v)
+                    .ok_or_else(|| E::invalid_value(serde::de::Unexpected::Str(v), &self))
+            }
+        }
+
+        deserializer.deserialize_any(Visitor)
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl From<chrono::DateTime<chrono::Utc>> for Timestamp {
+    fn from(date_time: chrono::DateTime<chrono::Utc>) -> Self {
+        Self::new(date_time.timestamp_millis())
+    }
+}
+
+#[cfg(feature = "chrono")]
+impl TryFrom<Timestamp> for chrono::DateTime<chrono::Utc> {
+    type Error = Error;
+
+    fn try_from(timestamp: Timestamp) -> Result<Self> {
+        Self::from_timestamp_millis(timestamp.timestamp)
+            .ok_or(Error::cbl_error(CouchbaseLiteError::InvalidParameter))
+    }
+}
+
+#[cfg(feature = "time")]
+impl From<time::OffsetDateTime> for Timestamp {
+    fn from(date_time: time::OffsetDateTime) -> Self {
+        Self::new(date_time.unix_timestamp_nanos().div_euclid(1_000_000) as i64)
+    }
+}
+
+#[cfg(feature = "time")]
+impl TryFrom<Timestamp> for time::OffsetDateTime {
+    type Error = Error;
+
+    fn try_from(timestamp: Timestamp) -> Result<Self> {
+        Self::from_unix_timestamp_nanos(i128::from(timestamp.timestamp) * 1_000_000)
+            .map_err(|_| Error::cbl_error(CouchbaseLiteError::InvalidParameter))
+    }
+}
+
 pub struct Listener<T> {
     pub listener_token: ListenerToken,
     pub listener: T,
@@ -20,8 +20,9 @@ use crate::c_api::{
     kCBLLogDomainMaskAll, kCBLLogDomainMaskDatabase, kCBLLogDomainMaskListener,
     kCBLLogDomainMaskNetwork, kCBLLogDomainMaskQuery, kCBLLogDomainMaskReplicator,
     CBLConsoleLogSink, CBLCustomLogSink, CBLLogDomain, CBLLogLevel, CBLLogSinks_SetConsole,
-    CBLLogSinks_SetCustom, FLString,
+    CBLLogSinks_SetCustom, CBL_LogMessage, FLString,
 };
+use crate::{CblRef, slice::from_str};
 
 use enum_primitive::FromPrimitive;
 
@@ -112,6 +113,11 @@ pub fn set_custom_log_sink(log_sink: CustomLogSink) {
     }
 }
 
+/** Writes a message to the log sinks, like the messages logged by Couchbase Lite itself. */
+pub fn write(domain: Domain, level: Level, message: &str) {
+    unsafe { CBL_LogMessage(domain as u8, level as u8, from_str(message).get_ref()) }
+}
+
 //////// INTERNALS:
 
 static mut LOG_CALLBACK: LogCallback = None;
@@ -0,0 +1,283 @@
+//! Keyset ("seek") pagination: instead of skipping rows with `OFFSET`, each page starts right
+//! after the sort key of the previous page's last row, so every page costs the same when the
+//! sort key is indexed.
+
+use crate::{
+    CouchbaseLiteError, Database, Error, Fleece, MutableArray, MutableDict, OwnedRow, Query,
+    QueryLanguage, Result,
+    fleece::FleeceReference,
+    query_builder::{Expression, Literal, Meta, OrderBy, QueryBuilder, SelectResult},
+};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+// Hidden result columns and parameters holding the position of the last row.
+const KEY_NAME: &str = "_page_key";
+const ID_NAME: &str = "_page_id";
+
+/** Pages through the results of a query ordered by a key.
+The document ID is used as a tie-breaker, so the key doesn't need to be unique. Documents
+whose key is null or missing come first, or last in descending order, like in N1QL, ordered by
+document ID. Use a value index on the key expression (see `Collection::create_index`) so each
+page is a seek in the index. */
+pub struct Paginator {
+    phases: [Phase; 2],
+    parameters: BTreeMap<String, Literal>,
+    columns: Vec<String>,
+    page_size: u32,
+}
+
+// The queries of the rows whose key is null or missing (not `valued`), or of the other rows.
+struct Phase {
+    valued: bool,
+    first: Query,
+    next: Query,
+}
+
+impl Paginator {
+    /** Creates a paginator from a query, the sort key, and the number of rows per page.
+    The ordering and limit of the query are replaced; a query with an offset is rejected with
+    `InvalidParameter`. */
+    pub fn new(db: &Database, query: QueryBuilder, key: OrderBy, page_size: u32) -> Result<Self> {
+        if page_size == 0 || query.offset.is_some() {
+            return Err(Error::cbl_error(CouchbaseLiteError::InvalidParameter));
+        }
+
+        let OrderBy {
+            expression: key,
+            descending,
+        } = key;
+        let id = match query.from.as_ref() {
+            Some(from) if !query.joins.is_empty() => Meta::id().of(from.effective_alias()),
+            _ => Meta::id(),
+        };
+        let after = |a: Expression, b: Expression| {
+            if descending {
+                a.less_than(b)
+            } else {
+                a.greater_than(b)
+            }
+        };
+
+        let paged = |condition: Expression, order_by: Vec<Expression>| {
+            let mut builder = query.clone();
+            builder.where_ = Some(match builder.where_.take() {
+                Some(existing) => existing.and(condition),
+                None => condition,
+            });
+            builder
+                .select
+                .push(SelectResult::expression(key.clone()).alias(KEY_NAME));
+            builder
+                .select
+                .push(SelectResult::expression(id.clone()).alias(ID_NAME));
+            builder.order_by = order_by
+                .into_iter()
+                .map(|expression| OrderBy {
+                    expression,
+                    descending,
+                })
+                .collect();
+            builder.limit(page_size)
+        };
+
+        let valued = key.clone().is_not_null().and(key.clone().is_not_missing());
+        let seek = after(key.clone(), Expression::parameter(KEY_NAME)).or(key
+            .clone()
+            .equal_to(Expression::parameter(KEY_NAME))
+            .and(after(id.clone(), Expression::parameter(ID_NAME))));
+        let valued = Phase {
+            valued: true,
+            first: paged(valued.clone(), vec![key.clone(), id.clone()])
+                .build(db, QueryLanguage::N1QL)?,
+            next: paged(valued.and(seek), vec![key.clone(), id.clone()])
+                .set_parameter(KEY_NAME, Literal::Null)
+                .set_parameter(ID_NAME, Literal::Null)
+                .build(db, QueryLanguage::N1QL)?,
+        };
+
+        let unvalued = key.clone().is_null().or(key.clone().is_missing());
+        let seek = after(id.clone(), Expression::parameter(ID_NAME));
+        let unvalued = Phase {
+            valued: false,
+            first: paged(unvalued.clone(), vec![id.clone()]).build(db, QueryLanguage::N1QL)?,
+            next: paged(unvalued.and(seek), vec![id.clone()])
+                .set_parameter(ID_NAME, Literal::Null)
+                .build(db, QueryLanguage::N1QL)?,
+        };
+
+        let mut columns: Vec<String> = valued
+            .first
+            .column_names()
+            .into_iter()
+            .map(String::from)
+            .collect();
+        columns.truncate(columns.len() - 2);
+        Ok(Self {
+            phases: if descending {
+                [valued, unvalued]
+            } else {
+                [unvalued, valued]
+            },
+            parameters: query.parameters,
+            columns,
+            page_size,
+        })
+    }
+
+    /** Returns the page starting after the cursor, or the first page without cursor. */
+    pub fn page(&self, cursor: Option<&Cursor>) -> Result<Page> {
+        let position = cursor.map(Cursor::position).transpose()?;
+        let (mut phase, mut after) = match &position {
+            None => (0, None),
+            Some(position) => {
+                let position = position.as_array();
+                let valued = position.get(0).as_bool_or_false();
+                let phase = self
+                    .phases
+                    .iter()
+                    .position(|phase| phase.valued == valued)
+                    .unwrap_or_default();
+                (phase, Some(position))
+            }
+        };
+
+        let page_size = self.page_size as usize;
+        let mut rows = Vec::new();
+        let mut last = None;
+        // The rows of the next phase complete a page ending the previous one.
+        while rows.len() < page_size && phase < self.phases.len() {
+            let Phase {
+                valued,
+                first,
+                next,
+            } = &self.phases[phase];
+            let mut parameters = MutableDict::new();
+            for (name, value) in &self.parameters {
+                value.put(parameters.at(name));
+            }
+            let query = match after.take() {
+                None => first,
+                Some(position) => {
+                    if *valued {
+                        parameters.at(KEY_NAME).put_value(&position.get(1));
+                    }
+                    parameters.at(ID_NAME).put_value(&position.get(2));
+                    next
+                }
+            };
+            query.set_parameters(&parameters);
+
+            for row in query.execute()?.take(page_size - rows.len()) {
+                let values = row.as_array();
+                let count = self.columns.len() as u32;
+                let mut columns = MutableArray::new();
+                for i in 0..count {
+                    columns.append().put_value(&values.get(i));
+                }
+                rows.push(OwnedRow::from_parts(self.columns.clone(), &columns)?);
+
+                let mut position = MutableArray::new();
+                position.append().put_bool(*valued);
+                if *valued {
+                    position.append().put_value(&values.get(count));
+                } else {
+                    position.append().put_null();
+                }
+                position.append().put_value(&values.get(count + 1));
+                last = Some(Cursor(position.to_json()));
+            }
+            phase += 1;
+        }
+
+        let next = if rows.len() == page_size { last } else { None };
+        Ok(Page { rows, next })
+    }
+
+    /** Iterates over the pages, starting after the cursor or from the first page. */
+    pub const fn pages(&self, cursor: Option<Cursor>) -> Pages<'_> {
+        Pages {
+            paginator: self,
+            cursor,
+            done: false,
+        }
+    }
+}
+
+/** A page of results, with the cursor of the next page if there may be more rows. */
+#[derive(Debug)]
+pub struct Page {
+    pub rows: Vec<OwnedRow>,
+    pub next: Option<Cursor>,
+}
+
+/** An iterator over pages, returned by `Paginator::pages`. */
+pub struct Pages<'p> {
+    paginator: &'p Paginator,
+    cursor: Option<Cursor>,
+    done: bool,
+}
+
+impl Iterator for Pages<'_> {
+    type Item = Result<Page>;
+
+    fn next(&mut self) -> Option<Result<Page>> {
+        if self.done {
+            return None;
+        }
+        let page = self.paginator.page(self.cursor.as_ref());
+        match &page {
+            Ok(page) if page.next.is_some() => self.cursor.clone_from(&page.next),
+            _ => self.done = true,
+        }
+        match page {
+            Ok(page) if page.rows.is_empty() => None,
+            page => Some(page),
+        }
+    }
+}
+
+/** An opaque position in the results, to resume pagination later.
+It can be stored as a string with `to_string` and restored with `parse`. */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cursor(String);
+
+impl Cursor {
+    // The position is the JSON array `[key is valued, key, document ID]` of the last row.
+    fn position(&self) -> Result<Fleece> {
+        let position = Fleece::parse_json(&self.0)?;
+        if position.as_array().count() != 3 {
+            return Err(Error::cbl_error(CouchbaseLiteError::InvalidParameter));
+        }
+        Ok(position)
+    }
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.bytes().try_for_each(|b| write!(f, "{b:02x}"))
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = || Error::cbl_error(CouchbaseLiteError::InvalidParameter);
+        if !s.len().is_multiple_of(2) {
+            return Err(invalid());
+        }
+        let bytes = (0..s.len())
+            .step_by(2)
+            .map(|i| {
+                s.get(i..i + 2)
+                    .and_then(|b| u8::from_str_radix(b, 16).ok())
+                    .ok_or_else(invalid)
+            })
+            .collect::<Result<Vec<u8>>>()?;
+        let cursor = Self(String::from_utf8(bytes).map_err(|_| invalid())?);
+        cursor.position()?;
+        Ok(cursor)
+    }
+}
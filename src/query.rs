@@ -16,8 +16,8 @@
 //
 
 use crate::{
-    Array, CblRef, CouchbaseLiteError, Database, Dict, Error, MutableDict, Result, Value, failure,
-    release, retain,
+    Array, ValueType, CblRef, CouchbaseLiteError, Database, Dict, Error, Fleece, MutableDict,
+    Result, Value, failure, release, retain,
     slice::from_str,
     c_api::{
         CBLDatabase_CreateQuery, CBLError, CBLQuery, CBLQueryLanguage, CBLQuery_ColumnCount,
@@ -27,13 +27,25 @@ use crate::{
         CBLResultSet_ValueForKey, CBLListenerToken, CBLQuery_AddChangeListener,
         CBLQuery_CopyCurrentResults,
     },
-    Listener,
+    FleeceReference, Listener,
+    query_plan::QueryPlan,
 };
 
+use serde::{Serialize, de::DeserializeOwned};
+use std::cell::RefCell;
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::mem::ManuallyDrop;
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, mpsc};
+use std::task::Waker;
+use std::thread;
+use std::time::{Duration, Instant};
 use std::os::raw::c_uint;
 use crate::ListenerToken;
 
 /** Query languages. */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum QueryLanguage {
     JSON, // JSON query schema: github.com/couchbase/couchbase-lite-core/wiki/JSON-Query-Schema
     N1QL, // N1QL syntax: docs.couchbase.com/server/6.0/n1ql/n1ql-language-reference/index.html
@@ -49,7 +61,8 @@ unsafe extern "C" fn c_query_change_listener(
 ) {
     let callback = context as *const ChangeListener;
     let query = Query::reference(query.cast::<CBLQuery>());
-    let token = ListenerToken::new(token);
+    // The token is owned by the `Listener`: dropping it here would remove the listener.
+    let token = ManuallyDrop::new(ListenerToken::new(token));
 
     unsafe {
         (*callback)(&query, &token);
@@ -59,6 +72,9 @@ unsafe extern "C" fn c_query_change_listener(
 /** A compiled database query. */
 pub struct Query {
     cbl_ref: *mut CBLQuery,
+    // The names of the parameters, read from the query text; unknown for a query handed over
+    // by Couchbase Lite, like the one of a change listener.
+    parameter_names: Option<Arc<[String]>>,
 }
 
 impl CblRef for Query {
@@ -75,8 +91,17 @@ impl Query {
     with only some values different, create one query with placeholder parameter(s), and substitute
     the desired value(s) with `set_parameters` before each time you run the query. */
     pub fn new(db: &Database, language: QueryLanguage, str: &str) -> Result<Self> {
+        Self::compile(db, language, str).map_err(|(error, _)| error)
+    }
+
+    // Like `new`, with the approximate position of a syntax error in characters, if known.
+    fn compile(
+        db: &Database,
+        language: QueryLanguage,
+        str: &str,
+    ) -> std::result::Result<Self, (Error, Option<usize>)> {
         unsafe {
-            let mut pos: i32 = 0;
+            let mut pos: i32 = -1;
             let mut err = CBLError::default();
             let q = CBLDatabase_CreateQuery(
                 db.get_ref(),
@@ -86,11 +111,17 @@ impl Query {
                 &mut err,
             );
             if q.is_null() {
-                // TODO: Return the error pos somehow
-                return failure(err);
+                return Err((Error::new(&err), usize::try_from(pos).ok()));
             }
 
-            Ok(Self::take_ownership(q))
+            let names = match language {
+                QueryLanguage::N1QL => Some(n1ql_parameter_names(str)),
+                QueryLanguage::JSON => json_parameter_names(str),
+            };
+            Ok(Self {
+                cbl_ref: q,
+                parameter_names: names.map(|names| names.into_iter().collect()),
+            })
         }
     }
 
@@ -98,14 +129,10 @@ impl Query {
     pub(crate) fn reference(cbl_ref: *mut CBLQuery) -> Self {
         Self {
             cbl_ref: unsafe { retain(cbl_ref) },
+            parameter_names: None,
         }
     }
 
-    /// Takes ownership of the CBL ref, the reference counter is not increased so dropping the instance will free the ref.
-    pub(crate) const fn take_ownership(cbl_ref: *mut CBLQuery) -> Self {
-        Self { cbl_ref }
-    }
-
     /** Assigns values to the query's parameters.
     These values will be substited for those parameters whenever the query is executed,
     until they are next assigned.
@@ -128,6 +155,54 @@ impl Query {
         }
     }
 
+    /** Assigns the query's parameters from a serializable value: a struct or a map whose keys
+    are the parameter names, like the ones built with the `params!` macro.
+    Fails with `BindError::MissingParameters` if a parameter referenced by the query is not
+    supplied, leaving the current parameters unchanged. */
+    pub fn bind<T: Serialize + ?Sized>(
+        &self,
+        parameters: &T,
+    ) -> std::result::Result<(), BindError> {
+        let json = serde_json::to_string(parameters)
+            .map_err(|_| Error::cbl_error(CouchbaseLiteError::InvalidParameter))?;
+        let parameters = MutableDict::from_json(&json)?;
+        let missing = self.missing_parameters(&parameters.as_dict())?;
+        if !missing.is_empty() {
+            return Err(BindError::MissingParameters(missing));
+        }
+        self.set_parameters(&parameters);
+        Ok(())
+    }
+
+    /** Returns the names of the parameters referenced by the query, like `name` for `$name`,
+    in alphabetical order.
+    The names are read from the query text. For a query that didn't come from `Query::new`,
+    like the one passed to a change listener, the text isn't available: the names are then
+    read from the SQLite statement in `explain`, where LiteCore writes a parameter `$name`
+    as `$_name`. */
+    pub fn parameter_names(&self) -> Result<Vec<String>> {
+        if let Some(names) = &self.parameter_names {
+            return Ok(names.to_vec());
+        }
+        let explain = self.explain()?;
+        let sql = explain.split("\n\n").next().unwrap_or_default();
+        Ok(n1ql_parameter_names(sql)
+            .into_iter()
+            .filter_map(|name| name.strip_prefix('_').map(String::from))
+            .filter(|name| !name.is_empty())
+            .collect())
+    }
+
+    /** Returns the names of the parameters referenced by the query that have no value in
+    `parameters`. */
+    pub fn missing_parameters(&self, parameters: &Dict) -> Result<Vec<String>> {
+        Ok(self
+            .parameter_names()?
+            .into_iter()
+            .filter(|name| !parameters.get(name))
+            .collect())
+    }
+
     /** Returns information about the query, including the translated SQLite form, and the search
     strategy. You can use this to help optimize the query: the word `SCAN` in the strategy
     indicates a linear scan of the entire database, which should be avoided by adding an index.
@@ -140,6 +215,12 @@ impl Query {
         }
     }
 
+    /** Returns the plan of the query, parsed from `explain`: the SQLite statement, and the
+    steps of the search strategy with the indexes they use. */
+    pub fn plan(&self) -> Result<QueryPlan> {
+        QueryPlan::parse(&self.explain()?)
+    }
+
     /** Runs the query, returning the results as a `ResultSet` object, which is an iterator
     of `Row` objects, each of which has column values. */
     pub fn execute(&self) -> Result<ResultSet> {
@@ -153,6 +234,18 @@ impl Query {
         }
     }
 
+    /** Runs the query, deserializing each row into `T` with serde. The row is seen as a
+    dictionary whose keys are the column names (see `column_names`), so the fields of a
+    struct are matched by name; use `AS` in the query to rename columns. */
+    pub fn execute_as<T: DeserializeOwned>(&self) -> Result<TypedResultSet<T>> {
+        Ok(TypedResultSet::new(self.execute()?, Row::deserialize))
+    }
+
+    /** Runs the query, converting each row into `T` with its `FromRow` implementation. */
+    pub fn execute_from_row<T: FromRow>(&self) -> Result<TypedResultSet<T>> {
+        Ok(TypedResultSet::new(self.execute()?, T::from_row))
+    }
+
     /** Returns the number of columns in each result.
     This comes directly from the number of "SELECT..." values in the query string. */
     pub fn column_count(&self) -> usize {
@@ -205,6 +298,77 @@ impl Query {
         }
     }
 
+    /** Turns the query into a live query, returning an observer that receives a fresh
+    `ResultSet` each time the results change, starting with the current results.
+    The observer is an `Iterator` blocking until the next results, and a `futures::Stream`
+    with the `async` feature. Only the latest results are kept: results that weren't received
+    before newer ones arrived are dropped. The query stops being observed when the observer is
+    dropped.
+
+    With a `debounce` delay, a burst of changes closer than the delay is delivered as a single
+    `ResultSet`, once the results have been stable for the delay. */
+    pub fn observe(&mut self, debounce: Option<Duration>) -> QueryObserver {
+        let shared = Arc::new(ObserverShared::default());
+        let deliver: Box<dyn Fn(Result<ResultSet>) + Send> = match debounce {
+            None => {
+                let shared = shared.clone();
+                Box::new(move |results| shared.push(results))
+            }
+            Some(delay) => {
+                let (sender, receiver) = mpsc::channel::<SendableResults>();
+                let shared = shared.clone();
+                thread::spawn(move || debounce_results(&receiver, delay, &shared));
+                let sender = Mutex::new(sender);
+                Box::new(move |results| {
+                    let _ = sender
+                        .lock()
+                        .map(|sender| sender.send(SendableResults(results)));
+                })
+            }
+        };
+        let listener = self.add_listener(Box::new(move |query, token| {
+            deliver(query.copy_current_results(token));
+        }));
+        QueryObserver {
+            shared,
+            _listener: listener,
+        }
+    }
+
+    /** Like `observe`, but delivers the rows added, removed and changed since the previous
+    results instead of the whole `ResultSet`, so a list can be updated incrementally. Rows are
+    matched between results by the value of the `key_column`, typically `meta().id` (named
+    `id`); the first diff has all the current rows as added.
+    Fails with `InvalidParameter` if the query has no such column. */
+    pub fn observe_diffs(
+        &mut self,
+        key_column: &str,
+        debounce: Option<Duration>,
+    ) -> Result<DiffObserver> {
+        let differ = RowsDiffer::new(self, key_column)?;
+        Ok(DiffObserver {
+            observer: self.observe(debounce),
+            differ,
+        })
+    }
+
+    /** Registers a change listener receiving the rows added, removed and changed since the
+    previous results, matched by the value of the `key_column` (see `observe_diffs`).
+    Fails with `InvalidParameter` if the query has no such column. */
+    pub fn add_diff_listener(
+        &mut self,
+        key_column: &str,
+        listener: DiffListener,
+    ) -> Result<Listener<ChangeListener>> {
+        let differ = RefCell::new(RowsDiffer::new(self, key_column)?);
+        Ok(self.add_listener(Box::new(move |query, token| {
+            let diff = query
+                .copy_current_results(token)
+                .and_then(|results| differ.borrow_mut().update(results));
+            listener(query, diff);
+        })))
+    }
+
     pub fn copy_current_results(&self, listener: &ListenerToken) -> Result<ResultSet> {
         let mut error = CBLError::default();
         let result =
@@ -229,9 +393,541 @@ impl Clone for Query {
         unsafe {
             Self {
                 cbl_ref: retain(self.get_ref()),
+                parameter_names: self.parameter_names.clone(),
+            }
+        }
+    }
+}
+
+// The parameters of a N1QL query: `$name` outside of string literals, escaped identifiers
+// and comments.
+fn n1ql_parameter_names(text: &str) -> BTreeSet<String> {
+    let bytes = text.as_bytes();
+    let mut names = BTreeSet::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            quote @ (b'\'' | b'"' | b'`') => {
+                i += 1;
+                while i < bytes.len() {
+                    if bytes[i] == b'\\' {
+                        i += 1;
+                    } else if bytes[i] == quote {
+                        if bytes.get(i + 1) != Some(&quote) {
+                            break;
+                        }
+                        i += 1;
+                    }
+                    i += 1;
+                }
+            }
+            b'-' if bytes.get(i + 1) == Some(&b'-') => {
+                i = text[i..].find('\n').map_or(bytes.len(), |n| i + n);
+            }
+            b'/' if bytes.get(i + 1) == Some(&b'*') => {
+                i = text[i + 2..].find("*/").map_or(bytes.len(), |n| i + n + 3);
+            }
+            b'$' => {
+                let rest = &text[i + 1..];
+                let len = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                if len > 0 {
+                    names.insert(rest[..len].to_string());
+                }
+                i += len;
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+    names
+}
+
+// The parameters of a JSON query: the operations `["$name"]`.
+fn json_parameter_names(text: &str) -> Option<BTreeSet<String>> {
+    fn collect(value: &serde_json::Value, names: &mut BTreeSet<String>) {
+        if let serde_json::Value::Array(items) = value {
+            if let Some(serde_json::Value::String(op)) = items.first()
+                && let Some(name) = op.strip_prefix('$').filter(|name| !name.is_empty())
+            {
+                names.insert(name.to_string());
+            }
+            items.iter().for_each(|item| collect(item, names));
+        } else if let serde_json::Value::Object(entries) = value {
+            entries.values().for_each(|item| collect(item, names));
+        }
+    }
+    let value = serde_json::from_str(text).ok()?;
+    let mut names = BTreeSet::new();
+    collect(&value, &mut names);
+    Some(names)
+}
+
+/** The reasons `Query::bind` can fail. */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BindError {
+    /** The parameters referenced by the query that have no value, in alphabetical order. */
+    MissingParameters(Vec<String>),
+    /** The parameters are not a JSON object, or the query can't be explained. */
+    Error(Error),
+}
+
+impl From<Error> for BindError {
+    fn from(error: Error) -> Self {
+        Self::Error(error)
+    }
+}
+
+impl fmt::Display for BindError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::MissingParameters(names) => {
+                write!(f, "missing query parameters: {}", names.join(", "))
+            }
+            Self::Error(error) => fmt::Display::fmt(error, f),
+        }
+    }
+}
+
+impl std::error::Error for BindError {}
+
+//////// QUERY VALIDATION:
+
+/** A problem found in a query by `validate`. */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryDiagnostic {
+    /** The error returned by `Query::new`. */
+    pub error: Error,
+    /** The approximate byte offset of the problem in the query, if known. Couchbase Lite
+    reports it as a character position, which is converted. */
+    pub offset: Option<usize>,
+    /** The line of the problem, starting at 1. */
+    pub line: usize,
+    /** The column of the problem in its line, in characters, starting at 1. */
+    pub column: usize,
+    pub message: String,
+    /** The line of the query with the problem, and a caret under its column below it.
+    Empty if the offset isn't known. */
+    pub excerpt: String,
+}
+
+impl QueryDiagnostic {
+    fn new(text: &str, error: Error, position: Option<usize>) -> Self {
+        let offset = position.map(|position| {
+            text.char_indices()
+                .nth(position)
+                .map_or(text.len(), |(offset, _)| offset)
+        });
+        let (line, column, excerpt) = offset.map_or((1, 1, String::new()), |offset| {
+            let line_start = text[..offset].rfind('\n').map_or(0, |i| i + 1);
+            let line_end = text[offset..].find('\n').map_or(text.len(), |i| offset + i);
+            let line = text[..offset].matches('\n').count() + 1;
+            let column = text[line_start..offset].chars().count() + 1;
+            let source = text[line_start..line_end].trim_end_matches('\r');
+            let excerpt = format!("{source}\n{}^", " ".repeat(column - 1));
+            (line, column, excerpt)
+        });
+        Self {
+            error,
+            offset,
+            line,
+            column,
+            message: error.message(),
+            excerpt,
+        }
+    }
+}
+
+impl fmt::Display for QueryDiagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.offset.is_some() {
+            write!(
+                f,
+                "line {}, column {}: {}\n{}",
+                self.line, self.column, self.message, self.excerpt
+            )
+        } else {
+            f.write_str(&self.message)
+        }
+    }
+}
+
+impl std::error::Error for QueryDiagnostic {}
+
+/** Compiles a SQL++ (N1QL) query to check it, without running it. The collections it reads
+must exist in the database. */
+pub fn validate(db: &Database, text: &str) -> std::result::Result<(), QueryDiagnostic> {
+    Query::compile(db, QueryLanguage::N1QL, text)
+        .map(|_| ())
+        .map_err(|(error, offset)| QueryDiagnostic::new(text, error, offset))
+}
+
+/** A new empty database in the temporary directory, deleted when dropped, to validate queries
+in tests, for instance the query constants of a crate:
+
+```no_run
+# use couchbase_lite::*;
+const HOTELS: &str = "SELECT name FROM travel.hotels WHERE city = $city";
+
+let validator = QueryValidator::new()
+    .unwrap()
+    .with_collection("travel", "hotels")
+    .unwrap();
+validator.assert_valid(&[("HOTELS", HOTELS)]);
+```
+*/
+pub struct QueryValidator {
+    db: Option<Database>,
+}
+
+impl QueryValidator {
+    pub fn new() -> Result<Self> {
+        static COUNTER: std::sync::atomic::AtomicUsize = std::sync::atomic::AtomicUsize::new(0);
+        let name = format!(
+            "query_validator_{}_{}",
+            std::process::id(),
+            COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed)
+        );
+        let directory = std::env::temp_dir();
+        Database::delete_file(&name, &directory)?;
+        let db = Database::open(
+            &name,
+            Some(crate::DatabaseConfiguration {
+                directory: &directory,
+                #[cfg(feature = "enterprise")]
+                encryption_key: None,
+            }),
+        )?;
+        Ok(Self { db: Some(db) })
+    }
+
+    /** Creates a collection read by the queries. */
+    pub fn with_collection(self, scope: &str, collection: &str) -> Result<Self> {
+        self.database()
+            .create_collection(collection.to_string(), scope.to_string())?;
+        Ok(self)
+    }
+
+    /** Returns the scratch database, to create the indexes used by `MATCH()` for instance. */
+    pub fn database(&self) -> &Database {
+        self.db.as_ref().expect("database deleted on drop only")
+    }
+
+    pub fn validate(&self, text: &str) -> std::result::Result<(), QueryDiagnostic> {
+        validate(self.database(), text)
+    }
+
+    /** Panics with the diagnostics of the invalid queries, given with their names. */
+    #[track_caller]
+    pub fn assert_valid(&self, queries: &[(&str, &str)]) {
+        let problems: Vec<String> = queries
+            .iter()
+            .filter_map(|(name, text)| {
+                self.validate(text)
+                    .err()
+                    .map(|diagnostic| format!("{name}: {diagnostic}"))
+            })
+            .collect();
+        assert!(
+            problems.is_empty(),
+            "{} invalid queries:\n{}",
+            problems.len(),
+            problems.join("\n")
+        );
+    }
+}
+
+impl Drop for QueryValidator {
+    fn drop(&mut self) {
+        if let Some(db) = self.db.take() {
+            let _ = db.delete();
+        }
+    }
+}
+
+//////// LIVE QUERY OBSERVER:
+
+// A result set is only used by one thread at a time: the notifying thread hands it over.
+struct SendableResults(Result<ResultSet>);
+
+unsafe impl Send for SendableResults {}
+
+#[derive(Default)]
+struct ObserverShared {
+    state: Mutex<ObserverState>,
+    available: Condvar,
+}
+
+#[derive(Default)]
+struct ObserverState {
+    // Only the latest results not received yet are kept.
+    results: Option<SendableResults>,
+    waker: Option<Waker>,
+}
+
+impl ObserverShared {
+    fn lock(&self) -> MutexGuard<'_, ObserverState> {
+        self.state
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    fn push(&self, results: Result<ResultSet>) {
+        let mut state = self.lock();
+        state.results = Some(SendableResults(results));
+        if let Some(waker) = state.waker.take() {
+            waker.wake();
+        }
+        self.available.notify_all();
+    }
+}
+
+// Delivers the last results received once none has been received for `delay`.
+// Stops when the listener, owning the sender, is removed.
+fn debounce_results(
+    receiver: &mpsc::Receiver<SendableResults>,
+    delay: Duration,
+    shared: &ObserverShared,
+) {
+    while let Ok(mut latest) = receiver.recv() {
+        loop {
+            match receiver.recv_timeout(delay) {
+                Ok(results) => latest = results,
+                Err(mpsc::RecvTimeoutError::Timeout) => break,
+                Err(mpsc::RecvTimeoutError::Disconnected) => return,
+            }
+        }
+        shared.push(latest.0);
+    }
+}
+
+/** Receives the results of a live query, returned by `Query::observe`.
+Iterating blocks until the next results are available. Dropping the observer removes the
+query listener. */
+pub struct QueryObserver {
+    shared: Arc<ObserverShared>,
+    _listener: Listener<ChangeListener>,
+}
+
+impl QueryObserver {
+    /** Returns the next results if they are already available, without blocking. */
+    pub fn try_recv(&self) -> Option<Result<ResultSet>> {
+        self.shared.lock().results.take().map(|results| results.0)
+    }
+
+    /** Waits for the next results, up to `timeout`. */
+    pub fn recv_timeout(&self, timeout: Duration) -> Option<Result<ResultSet>> {
+        let deadline = Instant::now() + timeout;
+        let mut state = self.shared.lock();
+        loop {
+            if let Some(results) = state.results.take() {
+                return Some(results.0);
+            }
+            let remaining = deadline.checked_duration_since(Instant::now())?;
+            state = self
+                .shared
+                .available
+                .wait_timeout(state, remaining)
+                .unwrap_or_else(std::sync::PoisonError::into_inner)
+                .0;
+        }
+    }
+}
+
+impl Iterator for QueryObserver {
+    type Item = Result<ResultSet>;
+
+    fn next(&mut self) -> Option<Result<ResultSet>> {
+        let mut state = self.shared.lock();
+        loop {
+            if let Some(results) = state.results.take() {
+                return Some(results.0);
+            }
+            state = self
+                .shared
+                .available
+                .wait(state)
+                .unwrap_or_else(std::sync::PoisonError::into_inner);
+        }
+    }
+}
+
+#[cfg(feature = "async")]
+impl futures_core::Stream for QueryObserver {
+    type Item = Result<ResultSet>;
+
+    fn poll_next(
+        self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Result<ResultSet>>> {
+        let mut state = self.shared.lock();
+        match state.results.take() {
+            Some(results) => std::task::Poll::Ready(Some(results.0)),
+            None => {
+                state.waker = Some(cx.waker().clone());
+                std::task::Poll::Pending
+            }
+        }
+    }
+}
+
+//////// LIVE QUERY DIFFS:
+
+pub type DiffListener = Box<dyn Fn(&Query, Result<RowsDiff>)>;
+
+/** The changes between two consecutive results of a live query, with rows matched by the
+value of a key column. Rows that only moved are not reported. */
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct RowsDiff {
+    /** Rows whose key isn't in the previous results, in the order of the new results. */
+    pub added: Vec<OwnedRow>,
+    /** Rows whose key isn't in the new results, in the order of the previous results. */
+    pub removed: Vec<OwnedRow>,
+    /** New versions of the rows whose values changed, in the order of the new results. */
+    pub changed: Vec<OwnedRow>,
+}
+
+impl RowsDiff {
+    /** Compares two lists of rows, matched by the value of the `key_column`. Keys should be
+    unique: when several rows share a key, only the last one is compared. */
+    pub fn between(previous: &[OwnedRow], current: &[OwnedRow], key_column: &str) -> Self {
+        let previous_rows: HashMap<String, &OwnedRow> = previous
+            .iter()
+            .map(|row| (row_key(row, key_column), row))
+            .collect();
+        let current_keys: HashSet<String> =
+            current.iter().map(|row| row_key(row, key_column)).collect();
+
+        let mut diff = Self::default();
+        for row in current {
+            match previous_rows.get(&row_key(row, key_column)) {
+                None => diff.added.push(row.clone()),
+                Some(old) if *old != row => diff.changed.push(row.clone()),
+                Some(_) => {}
             }
         }
+        diff.removed = previous
+            .iter()
+            .filter(|row| !current_keys.contains(&row_key(row, key_column)))
+            .cloned()
+            .collect();
+        diff
+    }
+
+    /** Returns true if no row was added, removed or changed. */
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+// The key of a row is the JSON of its key column; a missing value has an empty key.
+fn row_key(row: &OwnedRow, key_column: &str) -> String {
+    let key = row.get_key(key_column);
+    if key.get_type() == ValueType::Undefined {
+        String::new()
+    } else {
+        key.to_json()
+    }
+}
+
+// Keeps the previous rows of a live query, to diff them with the next results.
+struct RowsDiffer {
+    key_column: String,
+    previous: Vec<OwnedRow>,
+}
+
+impl RowsDiffer {
+    fn new(query: &Query, key_column: &str) -> Result<Self> {
+        if !query.column_names().contains(&key_column) {
+            return Err(Error::cbl_error(CouchbaseLiteError::InvalidParameter));
+        }
+        Ok(Self {
+            key_column: key_column.to_string(),
+            previous: Vec::new(),
+        })
+    }
+
+    fn update(&mut self, results: ResultSet) -> Result<RowsDiff> {
+        let current = results
+            .map(|row| row.to_owned())
+            .collect::<Result<Vec<_>>>()?;
+        let diff = RowsDiff::between(&self.previous, &current, &self.key_column);
+        self.previous = current;
+        Ok(diff)
+    }
+}
+
+/** Receives the changes of a live query's results, returned by `Query::observe_diffs`.
+Iterating blocks until the next changes are available. */
+pub struct DiffObserver {
+    observer: QueryObserver,
+    differ: RowsDiffer,
+}
+
+impl DiffObserver {
+    fn diff(&mut self, results: Result<ResultSet>) -> Result<RowsDiff> {
+        results.and_then(|results| self.differ.update(results))
     }
+
+    /** Returns the next changes if they are already available, without blocking. */
+    pub fn try_recv(&mut self) -> Option<Result<RowsDiff>> {
+        let results = self.observer.try_recv()?;
+        Some(self.diff(results))
+    }
+
+    /** Waits for the next changes, up to `timeout`. */
+    pub fn recv_timeout(&mut self, timeout: Duration) -> Option<Result<RowsDiff>> {
+        let results = self.observer.recv_timeout(timeout)?;
+        Some(self.diff(results))
+    }
+}
+
+impl Iterator for DiffObserver {
+    type Item = Result<RowsDiff>;
+
+    fn next(&mut self) -> Option<Result<RowsDiff>> {
+        let results = self.observer.next()?;
+        Some(self.diff(results))
+    }
+}
+
+#[cfg(feature = "async")]
+impl futures_core::Stream for DiffObserver {
+    type Item = Result<RowsDiff>;
+
+    fn poll_next(
+        mut self: std::pin::Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Option<Result<RowsDiff>>> {
+        std::pin::Pin::new(&mut self.observer)
+            .poll_next(cx)
+            .map(|results| results.map(|results| self.diff(results)))
+    }
+}
+
+/** Builds query parameters for `Query::bind`, from names and serializable values: scalars,
+`Timestamp`, `Vec`, maps or structs.
+
+```ignore
+query.bind(&params! { "min" => 18, "tags" => vec!["a", "b"] })?;
+```
+
+Panics if a value can't be serialized to JSON, like `serde_json::json!`. */
+#[macro_export]
+macro_rules! params {
+    ($($name:expr => $value:expr),* $(,)?) => {{
+        #[allow(unused_mut)]
+        let mut parameters = $crate::__private::serde_json::Map::new();
+        $(
+            parameters.insert(
+                ::std::string::ToString::to_string(&$name),
+                $crate::__private::serde_json::to_value(&$value)
+                    .expect("query parameter must be serializable"),
+            );
+        )*
+        $crate::__private::serde_json::Value::Object(parameters)
+    }};
 }
 
 //////// RESULT SET:
@@ -263,6 +959,47 @@ impl Iterator for ResultSet {
     }
 }
 
+impl ResultSet {
+    /** Consumes the remaining rows and returns them as a JSON array of objects keyed by
+    column name. */
+    pub fn collect_json(self) -> String {
+        let mut json = String::from("[");
+        for (i, row) in self.enumerate() {
+            if i > 0 {
+                json.push(',');
+            }
+            json.push_str(&row.as_dict().to_json());
+        }
+        json.push(']');
+        json
+    }
+
+    /** Returns the name of each column, or None for a column without name, so that the index
+    of a name is the index of its column. (This is the same as `Query`::column_names.) */
+    pub fn column_names(&self) -> Vec<Option<String>> {
+        unsafe {
+            let query = CBLResultSet_GetQuery(self.cbl_ref);
+            (0..CBLQuery_ColumnCount(query))
+                .map(|i| CBLQuery_ColumnName(query, i).as_str().map(String::from))
+                .collect()
+        }
+    }
+
+    pub(crate) fn column_labels(&self) -> Vec<String> {
+        column_labels(self.column_names())
+    }
+}
+
+// The column names, with `$N` for the unnamed column N, counted from 1, like the names
+// LiteCore gives to unnamed results.
+fn column_labels<S: Into<String>>(names: Vec<Option<S>>) -> Vec<String> {
+    names
+        .into_iter()
+        .enumerate()
+        .map(|(i, name)| name.map_or_else(|| format!("${}", i + 1), Into::into))
+        .collect()
+}
+
 impl Drop for ResultSet {
     fn drop(&mut self) {
         unsafe {
@@ -271,6 +1008,33 @@ impl Drop for ResultSet {
     }
 }
 
+/** An iterator over the rows of a query converted into `T`, returned by `Query::execute_as`
+and `Query::execute_from_row`. */
+pub struct TypedResultSet<T> {
+    results: ResultSet,
+    convert: fn(&Row) -> Result<T>,
+}
+
+impl<T> TypedResultSet<T> {
+    fn new(results: ResultSet, convert: fn(&Row) -> Result<T>) -> Self {
+        Self { results, convert }
+    }
+}
+
+impl<T> Iterator for TypedResultSet<T> {
+    type Item = Result<T>;
+
+    fn next(&mut self) -> Option<Result<T>> {
+        self.results.next().map(|row| (self.convert)(&row))
+    }
+}
+
+/** Conversion of a result row into a Rust value, for `Query::execute_from_row`.
+Implementations usually read the columns by name with `Row::get_key`. */
+pub trait FromRow: Sized {
+    fn from_row(row: &Row) -> Result<Self>;
+}
+
 //////// ROW:
 
 /** A single result row from a Query. */
@@ -313,6 +1077,27 @@ impl Row {
         }
     }
 
+    /** Returns the name of each column, or None for a column without name, so that the index
+    of a name is the index of its column. (This is the same as `ResultSet`::column_names.) */
+    pub fn column_names(&self) -> Vec<Option<&str>> {
+        (0..self.column_count())
+            .map(|i| self.column_name(i))
+            .collect()
+    }
+
+    /** Copies the row into an `OwnedRow`, which stays valid after the result set moves to the
+    next row, and can be sent to another thread. A column without name is named `$N`, N being
+    its position counted from 1. */
+    pub fn to_owned(&self) -> Result<OwnedRow> {
+        OwnedRow::from_parts(column_labels(self.column_names()), &self.as_array())
+    }
+
+    /** Deserializes the row with serde, as a dictionary keyed by column name. */
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T> {
+        serde_json::from_str(&self.as_dict().to_json())
+            .map_err(|_| Error::cbl_error(CouchbaseLiteError::InvalidParameter))
+    }
+
     /** Returns all of the columns as a Fleece array. */
     pub fn as_array(&self) -> Array {
         unsafe {
@@ -331,3 +1116,105 @@ impl Row {
         }
     }
 }
+
+//////// OWNED ROW:
+
+/** A copy of a result row, independent of its `ResultSet`. The values are stored in an
+immutable Fleece document, so owned rows can be kept, compared, and sent across threads. */
+#[derive(Clone)]
+pub struct OwnedRow {
+    columns: Vec<String>,
+    values: Fleece,
+}
+
+// The Fleece document is immutable and its reference count is atomic.
+unsafe impl Send for OwnedRow {}
+
+impl OwnedRow {
+    pub(crate) fn from_parts<V: FleeceReference>(columns: Vec<String>, values: &V) -> Result<Self> {
+        Ok(Self {
+            columns,
+            values: Fleece::from_value(values)?,
+        })
+    }
+
+    /** Returns the value of a column, given its (zero-based) index. */
+    pub fn get(&self, index: isize) -> Value {
+        self.as_array().get(index as u32)
+    }
+
+    /** Returns the value of a column, given its name. */
+    pub fn get_key(&self, key: &str) -> Value {
+        self.columns
+            .iter()
+            .position(|column| column == key)
+            .map_or(Value::UNDEFINED, |index| self.get(index as isize))
+    }
+
+    /** Returns the number of columns. */
+    pub fn column_count(&self) -> isize {
+        self.columns.len() as isize
+    }
+
+    /** Returns the name of a column. */
+    pub fn column_name(&self, col: isize) -> Option<&str> {
+        usize::try_from(col)
+            .ok()
+            .and_then(|col| self.columns.get(col))
+            .map(String::as_str)
+    }
+
+    /** Returns the column names. */
+    pub fn column_names(&self) -> Vec<&str> {
+        self.columns.iter().map(String::as_str).collect()
+    }
+
+    /** Returns all of the columns as a Fleece array. */
+    pub fn as_array(&self) -> Array {
+        self.values.as_array()
+    }
+
+    /** Returns all of the columns as a dictionary keyed by column name. Missing values are
+    omitted, like in `Row::as_dict`. */
+    pub fn to_dict(&self) -> MutableDict {
+        let mut dict = MutableDict::new();
+        for (index, column) in self.columns.iter().enumerate() {
+            let value = self.get(index as isize);
+            if value.get_type() == ValueType::Undefined {
+                continue;
+            }
+            dict.at(column).put_value(&value);
+        }
+        dict
+    }
+
+    /** Deserializes the row with serde, as a dictionary keyed by column name. */
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T> {
+        serde_json::from_str(&self.to_dict().to_json())
+            .map_err(|_| Error::cbl_error(CouchbaseLiteError::InvalidParameter))
+    }
+}
+
+impl PartialEq for OwnedRow {
+    fn eq(&self, other: &Self) -> bool {
+        self.columns == other.columns && self.as_array() == other.as_array()
+    }
+}
+
+impl Eq for OwnedRow {}
+
+impl Hash for OwnedRow {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.columns.hash(state);
+        self.as_array().hash(state);
+    }
+}
+
+impl fmt::Debug for OwnedRow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OwnedRow")
+            .field("columns", &self.columns)
+            .field("values", &self.as_array().to_json())
+            .finish()
+    }
+}
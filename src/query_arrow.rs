@@ -0,0 +1,302 @@
+//! Converts query results to Apache Arrow record batches, with the `arrow` feature.
+
+use crate::{
+    CouchbaseLiteError, Error, OwnedRow, Result, ResultSet, Value, ValueType,
+    fleece::FleeceReference,
+};
+use arrow_array::{
+    ArrayRef, RecordBatch,
+    builder::{
+        BinaryBuilder, BooleanBuilder, Float32Builder, Float64Builder, Int32Builder, Int64Builder,
+        LargeStringBuilder, StringBuilder, UInt64Builder,
+    },
+};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use std::collections::VecDeque;
+use std::sync::Arc;
+
+/** Options of the Arrow conversion. */
+#[derive(Debug, Clone)]
+pub struct ArrowOptions {
+    /** The maximum number of rows per record batch. */
+    pub batch_size: usize,
+    /** The number of rows the column types are inferred from, without explicit schema. */
+    pub infer_rows: usize,
+    /** The schema of the record batches. Its fields are matched to the result columns by
+    name; a field without column is null. The supported types are `Boolean`, `Int32`,
+    `Int64`, `UInt64`, `Float32`, `Float64`, `Utf8`, `LargeUtf8` and `Binary`. */
+    pub schema: Option<SchemaRef>,
+}
+
+impl Default for ArrowOptions {
+    fn default() -> Self {
+        Self {
+            batch_size: 1024,
+            infer_rows: 100,
+            schema: None,
+        }
+    }
+}
+
+impl ResultSet {
+    /** Converts the remaining rows to Arrow record batches, read one batch at a time.
+
+    Without explicit schema, the type of each column is inferred from the first rows: booleans,
+    integers as `Int64`, numbers as `Float64`, strings, and data as `Binary`. Arrays,
+    dictionaries and columns of mixed types are `Utf8`, with non-string values as JSON.
+    A value that doesn't fit the type of its column fails its batch with `InvalidParameter`. */
+    pub fn record_batches(mut self, options: &ArrowOptions) -> Result<RecordBatches> {
+        let names = self.column_labels();
+        let mut pending = VecDeque::new();
+        let (schema, columns) = if let Some(schema) = &options.schema {
+            let columns = schema
+                .fields()
+                .iter()
+                .map(|field| names.iter().position(|name| name == field.name()))
+                .collect();
+            (schema.clone(), columns)
+        } else {
+            for row in self.by_ref().take(options.infer_rows) {
+                pending.push_back(row.to_owned()?);
+            }
+            let fields: Vec<Field> = names
+                .iter()
+                .enumerate()
+                .map(|(i, name)| {
+                    let values = pending.iter().map(|row: &OwnedRow| row.get(i as isize));
+                    Field::new(name, infer_type(values), true)
+                })
+                .collect();
+            (
+                Arc::new(Schema::new(fields)),
+                (0..names.len()).map(Some).collect(),
+            )
+        };
+        // Fail early on unsupported types:
+        for field in schema.fields() {
+            ColumnBuilder::new(field.data_type(), 0)?;
+        }
+
+        Ok(RecordBatches {
+            results: self,
+            pending,
+            schema,
+            columns,
+            batch_size: options.batch_size.max(1),
+            done: false,
+        })
+    }
+
+    /** Converts the remaining rows to Arrow record batches, see `record_batches`. */
+    pub fn to_record_batches(self, options: &ArrowOptions) -> Result<Vec<RecordBatch>> {
+        self.record_batches(options)?.collect()
+    }
+}
+
+/** An iterator over the record batches of query results, returned by
+`ResultSet::record_batches`. */
+pub struct RecordBatches {
+    results: ResultSet,
+    // The rows read to infer the schema.
+    pending: VecDeque<OwnedRow>,
+    schema: SchemaRef,
+    // The result column of each field.
+    columns: Vec<Option<usize>>,
+    batch_size: usize,
+    done: bool,
+}
+
+impl RecordBatches {
+    /** Returns the schema of the record batches. */
+    pub fn schema(&self) -> SchemaRef {
+        self.schema.clone()
+    }
+
+    fn next_batch(&mut self) -> Result<Option<RecordBatch>> {
+        let mut builders = self
+            .schema
+            .fields()
+            .iter()
+            .map(|field| ColumnBuilder::new(field.data_type(), self.batch_size))
+            .collect::<Result<Vec<_>>>()?;
+
+        let mut count = 0;
+        while count < self.batch_size {
+            if let Some(row) = self.pending.pop_front() {
+                append_row(&mut builders, &self.columns, |i| row.get(i as isize))?;
+            } else if let Some(row) = self.results.next() {
+                append_row(&mut builders, &self.columns, |i| row.get(i as isize))?;
+            } else {
+                break;
+            }
+            count += 1;
+        }
+        if count == 0 {
+            return Ok(None);
+        }
+
+        let arrays = builders.into_iter().map(ColumnBuilder::finish).collect();
+        RecordBatch::try_new(self.schema.clone(), arrays)
+            .map(Some)
+            .map_err(|_| Error::cbl_error(CouchbaseLiteError::InvalidParameter))
+    }
+}
+
+impl Iterator for RecordBatches {
+    type Item = Result<RecordBatch>;
+
+    fn next(&mut self) -> Option<Result<RecordBatch>> {
+        if self.done {
+            return None;
+        }
+        let batch = self.next_batch();
+        if !matches!(batch, Ok(Some(_))) {
+            self.done = true;
+        }
+        batch.transpose()
+    }
+}
+
+fn append_row(
+    builders: &mut [ColumnBuilder],
+    columns: &[Option<usize>],
+    get: impl Fn(usize) -> Value,
+) -> Result<()> {
+    for (builder, column) in builders.iter_mut().zip(columns) {
+        match column {
+            Some(i) => builder.append(&get(*i))?,
+            None => builder.append_null(),
+        }
+    }
+    Ok(())
+}
+
+fn infer_type(values: impl Iterator<Item = Value>) -> DataType {
+    let mut inferred: Option<DataType> = None;
+    for value in values {
+        let data_type = match value.get_type() {
+            ValueType::Undefined | ValueType::Null => continue,
+            ValueType::Bool => DataType::Boolean,
+            ValueType::Number if value.as_i64().is_some() && !is_large_unsigned(&value) => {
+                DataType::Int64
+            }
+            ValueType::Number => DataType::Float64,
+            ValueType::Data => DataType::Binary,
+            ValueType::String | ValueType::Array | ValueType::Dict => DataType::Utf8,
+        };
+        inferred = Some(match inferred {
+            None => data_type,
+            Some(previous) if previous == data_type => previous,
+            Some(DataType::Int64 | DataType::Float64)
+                if matches!(data_type, DataType::Int64 | DataType::Float64) =>
+            {
+                DataType::Float64
+            }
+            Some(_) => DataType::Utf8,
+        });
+    }
+    inferred.unwrap_or(DataType::Utf8)
+}
+
+// Integers above `i64::MAX` are stored as unsigned.
+fn is_large_unsigned(value: &Value) -> bool {
+    value.is_unsigned() && i64::try_from(value.as_u64_or_0()).is_err()
+}
+
+// Strings as they are, other values as JSON.
+fn text(value: &Value) -> String {
+    value
+        .as_string()
+        .map_or_else(|| value.to_json(), str::to_string)
+}
+
+enum ColumnBuilder {
+    Boolean(BooleanBuilder),
+    Int32(Int32Builder),
+    Int64(Int64Builder),
+    UInt64(UInt64Builder),
+    Float32(Float32Builder),
+    Float64(Float64Builder),
+    Utf8(StringBuilder),
+    LargeUtf8(LargeStringBuilder),
+    Binary(BinaryBuilder),
+}
+
+impl ColumnBuilder {
+    fn new(data_type: &DataType, capacity: usize) -> Result<Self> {
+        Ok(match data_type {
+            DataType::Boolean => Self::Boolean(BooleanBuilder::with_capacity(capacity)),
+            DataType::Int32 => Self::Int32(Int32Builder::with_capacity(capacity)),
+            DataType::Int64 => Self::Int64(Int64Builder::with_capacity(capacity)),
+            DataType::UInt64 => Self::UInt64(UInt64Builder::with_capacity(capacity)),
+            DataType::Float32 => Self::Float32(Float32Builder::with_capacity(capacity)),
+            DataType::Float64 => Self::Float64(Float64Builder::with_capacity(capacity)),
+            DataType::Utf8 => Self::Utf8(StringBuilder::with_capacity(capacity, capacity * 16)),
+            DataType::LargeUtf8 => {
+                Self::LargeUtf8(LargeStringBuilder::with_capacity(capacity, capacity * 16))
+            }
+            DataType::Binary => Self::Binary(BinaryBuilder::with_capacity(capacity, capacity * 16)),
+            _ => return Err(Error::cbl_error(CouchbaseLiteError::InvalidParameter)),
+        })
+    }
+
+    fn append(&mut self, value: &Value) -> Result<()> {
+        if matches!(value.get_type(), ValueType::Undefined | ValueType::Null) {
+            self.append_null();
+            return Ok(());
+        }
+        let mismatch = || Error::cbl_error(CouchbaseLiteError::InvalidParameter);
+        let int = || value.as_i64().filter(|_| !is_large_unsigned(value));
+        match self {
+            Self::Boolean(builder) => builder.append_value(value.as_bool().ok_or_else(mismatch)?),
+            Self::Int32(builder) => builder.append_value(
+                int()
+                    .and_then(|int| i32::try_from(int).ok())
+                    .ok_or_else(mismatch)?,
+            ),
+            Self::Int64(builder) => builder.append_value(int().ok_or_else(mismatch)?),
+            Self::UInt64(builder) => {
+                let unsigned = if is_large_unsigned(value) {
+                    value.as_u64()
+                } else {
+                    int().and_then(|int| u64::try_from(int).ok())
+                };
+                builder.append_value(unsigned.ok_or_else(mismatch)?);
+            }
+            Self::Float32(builder) => builder.append_value(value.as_f32().ok_or_else(mismatch)?),
+            Self::Float64(builder) => builder.append_value(value.as_f64().ok_or_else(mismatch)?),
+            Self::Utf8(builder) => builder.append_value(text(value)),
+            Self::LargeUtf8(builder) => builder.append_value(text(value)),
+            Self::Binary(builder) => builder.append_value(value.as_data().ok_or_else(mismatch)?),
+        }
+        Ok(())
+    }
+
+    fn append_null(&mut self) {
+        match self {
+            Self::Boolean(builder) => builder.append_null(),
+            Self::Int32(builder) => builder.append_null(),
+            Self::Int64(builder) => builder.append_null(),
+            Self::UInt64(builder) => builder.append_null(),
+            Self::Float32(builder) => builder.append_null(),
+            Self::Float64(builder) => builder.append_null(),
+            Self::Utf8(builder) => builder.append_null(),
+            Self::LargeUtf8(builder) => builder.append_null(),
+            Self::Binary(builder) => builder.append_null(),
+        }
+    }
+
+    fn finish(self) -> ArrayRef {
+        match self {
+            Self::Boolean(mut builder) => Arc::new(builder.finish()),
+            Self::Int32(mut builder) => Arc::new(builder.finish()),
+            Self::Int64(mut builder) => Arc::new(builder.finish()),
+            Self::UInt64(mut builder) => Arc::new(builder.finish()),
+            Self::Float32(mut builder) => Arc::new(builder.finish()),
+            Self::Float64(mut builder) => Arc::new(builder.finish()),
+            Self::Utf8(mut builder) => Arc::new(builder.finish()),
+            Self::LargeUtf8(mut builder) => Arc::new(builder.finish()),
+            Self::Binary(mut builder) => Arc::new(builder.finish()),
+        }
+    }
+}
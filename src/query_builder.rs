@@ -0,0 +1,1282 @@
+//! A typed query builder, compiling to either SQL++ (N1QL) or the JSON query schema.
+//!
+//! ```ignore
+//! let query = QueryBuilder::select([
+//!     SelectResult::from(Meta::id()),
+//!     SelectResult::expression(Function::upper(Expression::property("name"))).alias("name"),
+//! ])
+//! .from(DataSource::collection(&collection))
+//! .where_(Expression::property("age").greater_than(Expression::parameter("age")))
+//! .order_by([OrderBy::desc(Expression::property("age"))])
+//! .limit(10)
+//! .set_parameter("age", 18)
+//! .build(&db, QueryLanguage::N1QL)?;
+//! ```
+
+use crate::{
+    CouchbaseLiteError, Database, Error, MutableDict, Query, QueryLanguage, Result, Slot,
+    collection::Collection,
+};
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops;
+
+//////// LITERALS
+
+/** A constant value used in a query expression or bound to a query parameter. */
+#[derive(Debug, Clone, PartialEq)]
+pub enum Literal {
+    Null,
+    Bool(bool),
+    Int(i64),
+    Double(f64),
+    String(String),
+}
+
+impl Literal {
+    fn write_n1ql(&self, out: &mut String) -> Result<()> {
+        match self {
+            Self::Null => out.push_str("NULL"),
+            Self::Bool(b) => out.push_str(if *b { "TRUE" } else { "FALSE" }),
+            Self::Int(i) => out.push_str(&i.to_string()),
+            Self::Double(d) => out.push_str(&format_double(*d)?),
+            Self::String(s) => {
+                out.push('\'');
+                out.push_str(&s.replace('\'', "''"));
+                out.push('\'');
+            }
+        }
+        Ok(())
+    }
+
+    fn write_json(&self, out: &mut String) -> Result<()> {
+        match self {
+            Self::Null => out.push_str("null"),
+            Self::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+            Self::Int(i) => out.push_str(&i.to_string()),
+            Self::Double(d) => out.push_str(&format_double(*d)?),
+            Self::String(s) => write_json_string(out, s),
+        }
+        Ok(())
+    }
+
+    pub(crate) fn put(&self, slot: Slot) {
+        match self {
+            Self::Null => slot.put_null(),
+            Self::Bool(b) => slot.put_bool(*b),
+            Self::Int(i) => slot.put_i64(*i),
+            Self::Double(d) => slot.put_f64(*d),
+            Self::String(s) => slot.put_string(s),
+        }
+    }
+}
+
+impl From<bool> for Literal {
+    fn from(value: bool) -> Self {
+        Self::Bool(value)
+    }
+}
+
+impl From<i32> for Literal {
+    fn from(value: i32) -> Self {
+        Self::Int(value.into())
+    }
+}
+
+impl From<u32> for Literal {
+    fn from(value: u32) -> Self {
+        Self::Int(value.into())
+    }
+}
+
+impl From<i64> for Literal {
+    fn from(value: i64) -> Self {
+        Self::Int(value)
+    }
+}
+
+impl From<f64> for Literal {
+    fn from(value: f64) -> Self {
+        Self::Double(value)
+    }
+}
+
+impl From<&str> for Literal {
+    fn from(value: &str) -> Self {
+        Self::String(value.to_string())
+    }
+}
+
+impl From<String> for Literal {
+    fn from(value: String) -> Self {
+        Self::String(value)
+    }
+}
+
+//////// EXPRESSIONS
+
+#[derive(Debug, Clone, Copy)]
+enum BinaryOp {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    And,
+    Or,
+    Like,
+    Concat,
+}
+
+impl BinaryOp {
+    const fn operator(self) -> &'static str {
+        match self {
+            Self::Equal => "=",
+            Self::NotEqual => "!=",
+            Self::Less => "<",
+            Self::LessOrEqual => "<=",
+            Self::Greater => ">",
+            Self::GreaterOrEqual => ">=",
+            Self::Add => "+",
+            Self::Subtract => "-",
+            Self::Multiply => "*",
+            Self::Divide => "/",
+            Self::Modulo => "%",
+            Self::And => "AND",
+            Self::Or => "OR",
+            Self::Like => "LIKE",
+            Self::Concat => "||",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum MetaField {
+    Id,
+    Sequence,
+    RevisionId,
+    Expiration,
+    Deleted,
+}
+
+impl MetaField {
+    const fn n1ql_name(self) -> &'static str {
+        match self {
+            Self::Id => "id",
+            Self::Sequence => "sequence",
+            Self::RevisionId => "revisionID",
+            Self::Expiration => "expiration",
+            Self::Deleted => "deleted",
+        }
+    }
+
+    const fn json_property(self) -> &'static str {
+        match self {
+            Self::Id => "_id",
+            Self::Sequence => "_sequence",
+            Self::RevisionId => "_revisionID",
+            Self::Expiration => "_expiration",
+            Self::Deleted => "_deleted",
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Literal(Literal),
+    Property {
+        alias: Option<String>,
+        path: Vec<String>,
+    },
+    All {
+        alias: Option<String>,
+    },
+    Meta {
+        alias: Option<String>,
+        field: MetaField,
+    },
+    Parameter(String),
+    Not(Box<Expression>),
+    Binary(BinaryOp, Box<Expression>, Box<Expression>),
+    Is {
+        operand: Box<Expression>,
+        negated: bool,
+        missing: bool,
+    },
+    Between(Box<Expression>, Box<Expression>, Box<Expression>),
+    In(Box<Expression>, Vec<Expression>),
+    Function(String, Vec<Expression>),
+}
+
+/** A query expression: a property, a literal, a parameter, or an operation on expressions.
+Plain values (numbers, strings, booleans) convert into literal expressions. */
+#[derive(Debug, Clone)]
+pub struct Expression(Expr);
+
+impl Expression {
+    /** A document property, given as a dot-separated path like `"address.city"`. */
+    pub fn property(path: &str) -> Self {
+        Self(Expr::Property {
+            alias: None,
+            path: path.split('.').map(str::to_string).collect(),
+        })
+    }
+
+    /** All the properties of the document, like `*` in `SELECT *`. */
+    pub const fn all() -> Self {
+        Self(Expr::All { alias: None })
+    }
+
+    /** A constant value. */
+    pub fn value(value: impl Into<Literal>) -> Self {
+        Self(Expr::Literal(value.into()))
+    }
+
+    pub const fn null() -> Self {
+        Self(Expr::Literal(Literal::Null))
+    }
+
+    /** A query parameter, whose value is bound with `QueryBuilder::set_parameter` or
+    `Query::set_parameters`. The name must be a valid identifier. */
+    pub fn parameter(name: &str) -> Self {
+        Self(Expr::Parameter(name.to_string()))
+    }
+
+    /** Qualifies a property, `all()` or `Meta` expression with the alias of a data source.
+    This is required to disambiguate properties in queries with joins. */
+    pub fn of(self, alias: &str) -> Self {
+        let alias = Some(alias.to_string());
+        Self(match self.0 {
+            Expr::Property { path, .. } => Expr::Property { alias, path },
+            Expr::All { .. } => Expr::All { alias },
+            Expr::Meta { field, .. } => Expr::Meta { alias, field },
+            other => other,
+        })
+    }
+
+    fn binary(self, op: BinaryOp, other: impl Into<Self>) -> Self {
+        Self(Expr::Binary(op, Box::new(self), Box::new(other.into())))
+    }
+
+    pub fn equal_to(self, other: impl Into<Self>) -> Self {
+        self.binary(BinaryOp::Equal, other)
+    }
+
+    pub fn not_equal_to(self, other: impl Into<Self>) -> Self {
+        self.binary(BinaryOp::NotEqual, other)
+    }
+
+    pub fn less_than(self, other: impl Into<Self>) -> Self {
+        self.binary(BinaryOp::Less, other)
+    }
+
+    pub fn less_than_or_equal_to(self, other: impl Into<Self>) -> Self {
+        self.binary(BinaryOp::LessOrEqual, other)
+    }
+
+    pub fn greater_than(self, other: impl Into<Self>) -> Self {
+        self.binary(BinaryOp::Greater, other)
+    }
+
+    pub fn greater_than_or_equal_to(self, other: impl Into<Self>) -> Self {
+        self.binary(BinaryOp::GreaterOrEqual, other)
+    }
+
+    pub fn and(self, other: impl Into<Self>) -> Self {
+        self.binary(BinaryOp::And, other)
+    }
+
+    pub fn or(self, other: impl Into<Self>) -> Self {
+        self.binary(BinaryOp::Or, other)
+    }
+
+    /** SQL `LIKE` pattern matching, where `%` matches any sequence and `_` any character. */
+    pub fn like(self, pattern: impl Into<Self>) -> Self {
+        self.binary(BinaryOp::Like, pattern)
+    }
+
+    /** String concatenation. */
+    pub fn concat(self, other: impl Into<Self>) -> Self {
+        self.binary(BinaryOp::Concat, other)
+    }
+
+    fn is(self, negated: bool, missing: bool) -> Self {
+        Self(Expr::Is {
+            operand: Box::new(self),
+            negated,
+            missing,
+        })
+    }
+
+    pub fn is_null(self) -> Self {
+        self.is(false, false)
+    }
+
+    pub fn is_not_null(self) -> Self {
+        self.is(true, false)
+    }
+
+    pub fn is_missing(self) -> Self {
+        self.is(false, true)
+    }
+
+    pub fn is_not_missing(self) -> Self {
+        self.is(true, true)
+    }
+
+    pub fn between(self, min: impl Into<Self>, max: impl Into<Self>) -> Self {
+        Self(Expr::Between(
+            Box::new(self),
+            Box::new(min.into()),
+            Box::new(max.into()),
+        ))
+    }
+
+    /** Tests whether the value is equal to one of the given values. */
+    pub fn is_in<T: Into<Self>>(self, values: impl IntoIterator<Item = T>) -> Self {
+        Self(Expr::In(
+            Box::new(self),
+            values.into_iter().map(Into::into).collect(),
+        ))
+    }
+
+    fn visit_parameters<'a>(&'a self, names: &mut BTreeSet<&'a str>) {
+        match &self.0 {
+            Expr::Parameter(name) => {
+                names.insert(name);
+            }
+            Expr::Not(operand) | Expr::Is { operand, .. } => operand.visit_parameters(names),
+            Expr::Binary(_, lhs, rhs) => {
+                lhs.visit_parameters(names);
+                rhs.visit_parameters(names);
+            }
+            Expr::Between(operand, min, max) => {
+                operand.visit_parameters(names);
+                min.visit_parameters(names);
+                max.visit_parameters(names);
+            }
+            Expr::In(operand, values) => {
+                operand.visit_parameters(names);
+                values.iter().for_each(|v| v.visit_parameters(names));
+            }
+            Expr::Function(_, args) => args.iter().for_each(|a| a.visit_parameters(names)),
+            Expr::Literal(_) | Expr::Property { .. } | Expr::All { .. } | Expr::Meta { .. } => {}
+        }
+    }
+
+    // Compound expressions are parenthesized when nested, so the operator precedence of the
+    // tree is kept.
+    fn write_n1ql(&self, out: &mut String, nested: bool) -> Result<()> {
+        let compound = matches!(
+            self.0,
+            Expr::Not(_) | Expr::Binary(..) | Expr::Is { .. } | Expr::Between(..) | Expr::In(..)
+        );
+        if nested && compound {
+            out.push('(');
+        }
+        match &self.0 {
+            Expr::Literal(literal) => literal.write_n1ql(out)?,
+            Expr::Property { alias, path } => {
+                let mut first = true;
+                for component in alias.iter().chain(path) {
+                    if !first {
+                        out.push('.');
+                    }
+                    first = false;
+                    write_n1ql_identifier(out, component)?;
+                }
+            }
+            Expr::All { alias } => {
+                if let Some(alias) = alias {
+                    write_n1ql_identifier(out, alias)?;
+                    out.push('.');
+                }
+                out.push('*');
+            }
+            Expr::Meta { alias, field } => {
+                out.push_str("meta(");
+                if let Some(alias) = alias {
+                    write_n1ql_identifier(out, alias)?;
+                }
+                out.push_str(").");
+                out.push_str(field.n1ql_name());
+            }
+            Expr::Parameter(name) => {
+                check_identifier(name)?;
+                out.push('$');
+                out.push_str(name);
+            }
+            Expr::Not(operand) => {
+                out.push_str("NOT ");
+                operand.write_n1ql(out, true)?;
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                lhs.write_n1ql(out, true)?;
+                out.push(' ');
+                out.push_str(op.operator());
+                out.push(' ');
+                rhs.write_n1ql(out, true)?;
+            }
+            Expr::Is {
+                operand,
+                negated,
+                missing,
+            } => {
+                operand.write_n1ql(out, true)?;
+                out.push_str(if *negated { " IS NOT " } else { " IS " });
+                out.push_str(if *missing { "MISSING" } else { "NULL" });
+            }
+            Expr::Between(operand, min, max) => {
+                operand.write_n1ql(out, true)?;
+                out.push_str(" BETWEEN ");
+                min.write_n1ql(out, true)?;
+                out.push_str(" AND ");
+                max.write_n1ql(out, true)?;
+            }
+            Expr::In(operand, values) => {
+                operand.write_n1ql(out, true)?;
+                out.push_str(" IN [");
+                write_n1ql_list(out, values)?;
+                out.push(']');
+            }
+            Expr::Function(name, args) => {
+                check_identifier(name)?;
+                out.push_str(name);
+                out.push('(');
+                write_n1ql_list(out, args)?;
+                out.push(')');
+            }
+        }
+        if nested && compound {
+            out.push(')');
+        }
+        Ok(())
+    }
+
+    fn write_json(&self, out: &mut String) -> Result<()> {
+        match &self.0 {
+            Expr::Literal(literal) => literal.write_json(out)?,
+            Expr::Property { alias, path } => {
+                let mut property = String::new();
+                for component in alias.iter().chain(path) {
+                    property.push('.');
+                    push_json_path_component(&mut property, component)?;
+                }
+                write_json_operation(out, &property, &[])?;
+            }
+            Expr::All { alias } => {
+                let mut property = String::from(".");
+                if let Some(alias) = alias {
+                    push_json_path_component(&mut property, alias)?;
+                    property.push('.');
+                }
+                write_json_operation(out, &property, &[])?;
+            }
+            Expr::Meta { alias, field } => {
+                let mut property = String::from(".");
+                if let Some(alias) = alias {
+                    push_json_path_component(&mut property, alias)?;
+                    property.push('.');
+                }
+                property.push_str(field.json_property());
+                write_json_operation(out, &property, &[])?;
+            }
+            Expr::Parameter(name) => {
+                check_identifier(name)?;
+                write_json_operation(out, &format!("${name}"), &[])?;
+            }
+            Expr::Not(operand) => write_json_operation(out, "NOT", &[operand])?,
+            Expr::Binary(op, lhs, rhs) => write_json_operation(out, op.operator(), &[lhs, rhs])?,
+            Expr::Is {
+                operand,
+                negated,
+                missing,
+            } => {
+                out.push('[');
+                write_json_string(out, if *negated { "IS NOT" } else { "IS" });
+                out.push(',');
+                operand.write_json(out)?;
+                out.push_str(if *missing {
+                    ",[\"MISSING\"]]"
+                } else {
+                    ",null]"
+                });
+            }
+            Expr::Between(operand, min, max) => {
+                write_json_operation(out, "BETWEEN", &[operand, min, max])?;
+            }
+            Expr::In(operand, values) => {
+                out.push_str("[\"IN\",");
+                operand.write_json(out)?;
+                out.push(',');
+                write_json_operation(out, "[]", &values.iter().collect::<Vec<_>>())?;
+                out.push(']');
+            }
+            Expr::Function(name, args) => {
+                check_identifier(name)?;
+                write_json_operation(out, &format!("{name}()"), &args.iter().collect::<Vec<_>>())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+macro_rules! literal_expression {
+    ($($t:ty),*) => {
+        $(
+            impl From<$t> for Expression {
+                fn from(value: $t) -> Self {
+                    Self::value(value)
+                }
+            }
+        )*
+    };
+}
+
+literal_expression!(Literal, bool, i32, u32, i64, f64, &str, String);
+
+impl ops::Not for Expression {
+    type Output = Self;
+    fn not(self) -> Self {
+        Self(Expr::Not(Box::new(self)))
+    }
+}
+
+impl<T: Into<Self>> ops::Add<T> for Expression {
+    type Output = Self;
+    fn add(self, other: T) -> Self {
+        self.binary(BinaryOp::Add, other)
+    }
+}
+
+impl<T: Into<Self>> ops::Sub<T> for Expression {
+    type Output = Self;
+    fn sub(self, other: T) -> Self {
+        self.binary(BinaryOp::Subtract, other)
+    }
+}
+
+impl<T: Into<Self>> ops::Mul<T> for Expression {
+    type Output = Self;
+    fn mul(self, other: T) -> Self {
+        self.binary(BinaryOp::Multiply, other)
+    }
+}
+
+impl<T: Into<Self>> ops::Div<T> for Expression {
+    type Output = Self;
+    fn div(self, other: T) -> Self {
+        self.binary(BinaryOp::Divide, other)
+    }
+}
+
+impl<T: Into<Self>> ops::Rem<T> for Expression {
+    type Output = Self;
+    fn rem(self, other: T) -> Self {
+        self.binary(BinaryOp::Modulo, other)
+    }
+}
+
+/** Document metadata expressions. Use `Expression::of` to qualify them with an alias. */
+pub struct Meta;
+
+impl Meta {
+    pub const fn id() -> Expression {
+        Expression(Expr::Meta {
+            alias: None,
+            field: MetaField::Id,
+        })
+    }
+
+    pub const fn sequence() -> Expression {
+        Expression(Expr::Meta {
+            alias: None,
+            field: MetaField::Sequence,
+        })
+    }
+
+    pub const fn revision_id() -> Expression {
+        Expression(Expr::Meta {
+            alias: None,
+            field: MetaField::RevisionId,
+        })
+    }
+
+    pub const fn expiration() -> Expression {
+        Expression(Expr::Meta {
+            alias: None,
+            field: MetaField::Expiration,
+        })
+    }
+
+    pub const fn deleted() -> Expression {
+        Expression(Expr::Meta {
+            alias: None,
+            field: MetaField::Deleted,
+        })
+    }
+}
+
+/** Query functions. Any function supported by SQL++ can be called with `Function::call`. */
+pub struct Function;
+
+impl Function {
+    /** Calls a function by name. The name must be a valid identifier. */
+    pub fn call(name: &str, args: impl IntoIterator<Item = Expression>) -> Expression {
+        Expression(Expr::Function(name.to_string(), args.into_iter().collect()))
+    }
+
+    // Aggregates:
+
+    /** Counts the results. Use `Expression::all()` to count all the rows. */
+    pub fn count(expression: Expression) -> Expression {
+        Self::call("COUNT", [expression])
+    }
+
+    pub fn sum(expression: Expression) -> Expression {
+        Self::call("SUM", [expression])
+    }
+
+    pub fn avg(expression: Expression) -> Expression {
+        Self::call("AVG", [expression])
+    }
+
+    pub fn min(expression: Expression) -> Expression {
+        Self::call("MIN", [expression])
+    }
+
+    pub fn max(expression: Expression) -> Expression {
+        Self::call("MAX", [expression])
+    }
+
+    // Numbers:
+
+    pub fn abs(expression: Expression) -> Expression {
+        Self::call("ABS", [expression])
+    }
+
+    pub fn round(expression: Expression) -> Expression {
+        Self::call("ROUND", [expression])
+    }
+
+    // Strings:
+
+    pub fn lower(expression: Expression) -> Expression {
+        Self::call("LOWER", [expression])
+    }
+
+    pub fn upper(expression: Expression) -> Expression {
+        Self::call("UPPER", [expression])
+    }
+
+    pub fn length(expression: Expression) -> Expression {
+        Self::call("LENGTH", [expression])
+    }
+
+    pub fn contains(expression: Expression, substring: impl Into<Expression>) -> Expression {
+        Self::call("CONTAINS", [expression, substring.into()])
+    }
+
+    // Arrays:
+
+    pub fn array_count(expression: Expression) -> Expression {
+        Self::call("ARRAY_COUNT", [expression])
+    }
+
+    pub fn array_contains(expression: Expression, value: impl Into<Expression>) -> Expression {
+        Self::call("ARRAY_CONTAINS", [expression, value.into()])
+    }
+
+    // Dates:
+
+    /** Parses an ISO-8601 date string into milliseconds since the Unix epoch. */
+    pub fn str_to_millis(expression: Expression) -> Expression {
+        Self::call("STR_TO_MILLIS", [expression])
+    }
+
+    /** Formats milliseconds since the Unix epoch as an ISO-8601 UTC date string. */
+    pub fn millis_to_utc(expression: Expression) -> Expression {
+        Self::call("MILLIS_TO_UTC", [expression])
+    }
+
+    // Full-text search:
+
+    /** Matches the full-text index with the given name against a search expression. */
+    pub fn full_text_match(index_name: &str, query: impl Into<Expression>) -> Expression {
+        Self::call("MATCH", [Expression::value(index_name), query.into()])
+    }
+
+    /** The relevance of a full-text match, to be used in `ORDER BY`. */
+    pub fn rank(index_name: &str) -> Expression {
+        Self::call("RANK", [Expression::value(index_name)])
+    }
+}
+
+//////// CLAUSES
+
+/** A column of the query results, optionally named with an alias. */
+#[derive(Debug, Clone)]
+pub struct SelectResult {
+    expression: Expression,
+    alias: Option<String>,
+}
+
+impl SelectResult {
+    pub const fn expression(expression: Expression) -> Self {
+        Self {
+            expression,
+            alias: None,
+        }
+    }
+
+    /** All the properties of the documents, like `SELECT *`. */
+    pub const fn all() -> Self {
+        Self::expression(Expression::all())
+    }
+
+    /** Names the column in the results. */
+    pub fn alias(mut self, alias: &str) -> Self {
+        self.alias = Some(alias.to_string());
+        self
+    }
+}
+
+impl From<Expression> for SelectResult {
+    fn from(expression: Expression) -> Self {
+        Self::expression(expression)
+    }
+}
+
+/** A collection to query, optionally named with an alias.
+Without alias, the collection name is used to qualify its properties. */
+#[derive(Debug, Clone)]
+pub struct DataSource {
+    scope: String,
+    collection: String,
+    alias: Option<String>,
+}
+
+impl DataSource {
+    pub fn collection(collection: &Collection) -> Self {
+        Self::named(&collection.scope().name(), &collection.name())
+    }
+
+    /** A collection given by its scope and collection names. */
+    pub fn named(scope: &str, collection: &str) -> Self {
+        Self {
+            scope: scope.to_string(),
+            collection: collection.to_string(),
+            alias: None,
+        }
+    }
+
+    pub fn alias(mut self, alias: &str) -> Self {
+        self.alias = Some(alias.to_string());
+        self
+    }
+
+    pub(crate) fn effective_alias(&self) -> &str {
+        self.alias.as_deref().unwrap_or(&self.collection)
+    }
+
+    fn write_n1ql(&self, out: &mut String) -> Result<()> {
+        write_n1ql_identifier(out, &self.scope)?;
+        out.push('.');
+        write_n1ql_identifier(out, &self.collection)?;
+        if let Some(alias) = &self.alias {
+            out.push_str(" AS ");
+            write_n1ql_identifier(out, alias)?;
+        }
+        Ok(())
+    }
+
+    // Writes the members of the JSON data source object, without the braces.
+    fn write_json(&self, out: &mut String) {
+        out.push_str("\"SCOPE\":");
+        write_json_string(out, &self.scope);
+        out.push_str(",\"COLLECTION\":");
+        write_json_string(out, &self.collection);
+        if let Some(alias) = &self.alias {
+            out.push_str(",\"AS\":");
+            write_json_string(out, alias);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum JoinKind {
+    Inner,
+    LeftOuter,
+    Cross,
+}
+
+impl JoinKind {
+    const fn keyword(self) -> &'static str {
+        match self {
+            Self::Inner => "INNER",
+            Self::LeftOuter => "LEFT OUTER",
+            Self::Cross => "CROSS",
+        }
+    }
+}
+
+/** A join with another collection. Inner and left outer joins require an `on` condition,
+cross joins must not have one. */
+#[derive(Debug, Clone)]
+pub struct Join {
+    kind: JoinKind,
+    source: DataSource,
+    on: Option<Expression>,
+}
+
+impl Join {
+    pub const fn inner(source: DataSource) -> Self {
+        Self {
+            kind: JoinKind::Inner,
+            source,
+            on: None,
+        }
+    }
+
+    pub const fn left_outer(source: DataSource) -> Self {
+        Self {
+            kind: JoinKind::LeftOuter,
+            source,
+            on: None,
+        }
+    }
+
+    pub const fn cross(source: DataSource) -> Self {
+        Self {
+            kind: JoinKind::Cross,
+            source,
+            on: None,
+        }
+    }
+
+    pub fn on(mut self, condition: Expression) -> Self {
+        self.on = Some(condition);
+        self
+    }
+}
+
+/** A sort criterion of the `ORDER BY` clause. Expressions convert into ascending order. */
+#[derive(Debug, Clone)]
+pub struct OrderBy {
+    pub(crate) expression: Expression,
+    pub(crate) descending: bool,
+}
+
+impl OrderBy {
+    pub const fn asc(expression: Expression) -> Self {
+        Self {
+            expression,
+            descending: false,
+        }
+    }
+
+    pub const fn desc(expression: Expression) -> Self {
+        Self {
+            expression,
+            descending: true,
+        }
+    }
+}
+
+impl From<Expression> for OrderBy {
+    fn from(expression: Expression) -> Self {
+        Self::asc(expression)
+    }
+}
+
+//////// QUERY BUILDER
+
+/** Builds a query from typed clauses, and compiles it to SQL++ (N1QL) or JSON.
+Identifiers (collections, aliases, properties) are always quoted, and the query is checked
+when compiled: a query without results or data source, a `HAVING` without `GROUP BY`,
+duplicate aliases, invalid joins or non-finite numbers are reported as `InvalidQuery`. */
+#[derive(Debug, Clone, Default)]
+pub struct QueryBuilder {
+    distinct: bool,
+    pub(crate) select: Vec<SelectResult>,
+    pub(crate) from: Option<DataSource>,
+    pub(crate) joins: Vec<Join>,
+    pub(crate) where_: Option<Expression>,
+    group_by: Vec<Expression>,
+    having: Option<Expression>,
+    pub(crate) order_by: Vec<OrderBy>,
+    pub(crate) limit: Option<Expression>,
+    pub(crate) offset: Option<Expression>,
+    pub(crate) parameters: BTreeMap<String, Literal>,
+}
+
+impl QueryBuilder {
+    pub fn select<T: Into<SelectResult>>(results: impl IntoIterator<Item = T>) -> Self {
+        Self {
+            select: results.into_iter().map(Into::into).collect(),
+            ..Default::default()
+        }
+    }
+
+    pub fn select_distinct<T: Into<SelectResult>>(results: impl IntoIterator<Item = T>) -> Self {
+        Self {
+            distinct: true,
+            ..Self::select(results)
+        }
+    }
+
+    pub fn from(mut self, source: DataSource) -> Self {
+        self.from = Some(source);
+        self
+    }
+
+    pub fn join(mut self, join: Join) -> Self {
+        self.joins.push(join);
+        self
+    }
+
+    pub fn where_(mut self, condition: Expression) -> Self {
+        self.where_ = Some(condition);
+        self
+    }
+
+    pub fn group_by(mut self, expressions: impl IntoIterator<Item = Expression>) -> Self {
+        self.group_by = expressions.into_iter().collect();
+        self
+    }
+
+    pub fn having(mut self, condition: Expression) -> Self {
+        self.having = Some(condition);
+        self
+    }
+
+    pub fn order_by<T: Into<OrderBy>>(mut self, orderings: impl IntoIterator<Item = T>) -> Self {
+        self.order_by = orderings.into_iter().map(Into::into).collect();
+        self
+    }
+
+    pub fn limit(mut self, limit: impl Into<Expression>) -> Self {
+        self.limit = Some(limit.into());
+        self
+    }
+
+    pub fn offset(mut self, offset: impl Into<Expression>) -> Self {
+        self.offset = Some(offset.into());
+        self
+    }
+
+    /** Binds the value of a parameter, set on the query by `build`. */
+    pub fn set_parameter(mut self, name: &str, value: impl Into<Literal>) -> Self {
+        self.parameters.insert(name.to_string(), value.into());
+        self
+    }
+
+    /** Returns the names of the parameters used in the query. */
+    pub fn parameter_names(&self) -> BTreeSet<&str> {
+        let mut names = BTreeSet::new();
+        for expression in self.expressions() {
+            expression.visit_parameters(&mut names);
+        }
+        names
+    }
+
+    /** Compiles the query to SQL++ (N1QL). */
+    pub fn to_n1ql(&self) -> Result<String> {
+        let from = self.check()?;
+        let mut out = String::from("SELECT ");
+        if self.distinct {
+            out.push_str("DISTINCT ");
+        }
+        for (i, result) in self.select.iter().enumerate() {
+            if i > 0 {
+                out.push_str(", ");
+            }
+            result.expression.write_n1ql(&mut out, false)?;
+            if let Some(alias) = &result.alias {
+                out.push_str(" AS ");
+                write_n1ql_identifier(&mut out, alias)?;
+            }
+        }
+        out.push_str(" FROM ");
+        from.write_n1ql(&mut out)?;
+        for join in &self.joins {
+            out.push(' ');
+            out.push_str(join.kind.keyword());
+            out.push_str(" JOIN ");
+            join.source.write_n1ql(&mut out)?;
+            if let Some(on) = &join.on {
+                out.push_str(" ON ");
+                on.write_n1ql(&mut out, false)?;
+            }
+        }
+        if let Some(condition) = &self.where_ {
+            out.push_str(" WHERE ");
+            condition.write_n1ql(&mut out, false)?;
+        }
+        if !self.group_by.is_empty() {
+            out.push_str(" GROUP BY ");
+            write_n1ql_list(&mut out, &self.group_by)?;
+        }
+        if let Some(condition) = &self.having {
+            out.push_str(" HAVING ");
+            condition.write_n1ql(&mut out, false)?;
+        }
+        if !self.order_by.is_empty() {
+            out.push_str(" ORDER BY ");
+            for (i, ordering) in self.order_by.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                ordering.expression.write_n1ql(&mut out, false)?;
+                if ordering.descending {
+                    out.push_str(" DESC");
+                }
+            }
+        }
+        if let Some(limit) = &self.limit {
+            out.push_str(" LIMIT ");
+            limit.write_n1ql(&mut out, false)?;
+        }
+        if let Some(offset) = &self.offset {
+            out.push_str(" OFFSET ");
+            offset.write_n1ql(&mut out, false)?;
+        }
+        Ok(out)
+    }
+
+    /** Compiles the query to the JSON query schema. */
+    pub fn to_json(&self) -> Result<String> {
+        let from = self.check()?;
+        let mut out = String::from("{\"WHAT\":[");
+        for (i, result) in self.select.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            match &result.alias {
+                Some(alias) => {
+                    out.push_str("[\"AS\",");
+                    result.expression.write_json(&mut out)?;
+                    out.push(',');
+                    write_json_string(&mut out, alias);
+                    out.push(']');
+                }
+                None => result.expression.write_json(&mut out)?,
+            }
+        }
+        out.push(']');
+        if self.distinct {
+            out.push_str(",\"DISTINCT\":true");
+        }
+        out.push_str(",\"FROM\":[{");
+        from.write_json(&mut out);
+        out.push('}');
+        for join in &self.joins {
+            out.push_str(",{");
+            join.source.write_json(&mut out);
+            out.push_str(",\"JOIN\":");
+            write_json_string(&mut out, join.kind.keyword());
+            if let Some(on) = &join.on {
+                out.push_str(",\"ON\":");
+                on.write_json(&mut out)?;
+            }
+            out.push('}');
+        }
+        out.push(']');
+        if let Some(condition) = &self.where_ {
+            out.push_str(",\"WHERE\":");
+            condition.write_json(&mut out)?;
+        }
+        if !self.group_by.is_empty() {
+            out.push_str(",\"GROUP_BY\":");
+            write_json_list(&mut out, self.group_by.iter())?;
+        }
+        if let Some(condition) = &self.having {
+            out.push_str(",\"HAVING\":");
+            condition.write_json(&mut out)?;
+        }
+        if !self.order_by.is_empty() {
+            out.push_str(",\"ORDER_BY\":[");
+            for (i, ordering) in self.order_by.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                if ordering.descending {
+                    write_json_operation(&mut out, "DESC", &[&ordering.expression])?;
+                } else {
+                    ordering.expression.write_json(&mut out)?;
+                }
+            }
+            out.push(']');
+        }
+        if let Some(limit) = &self.limit {
+            out.push_str(",\"LIMIT\":");
+            limit.write_json(&mut out)?;
+        }
+        if let Some(offset) = &self.offset {
+            out.push_str(",\"OFFSET\":");
+            offset.write_json(&mut out)?;
+        }
+        out.push('}');
+        Ok(out)
+    }
+
+    /** Compiles the query in the given language and binds the parameters set with
+    `set_parameter`. Fails with `InvalidParameter` if a parameter used in the query has no
+    value. */
+    pub fn build(&self, db: &Database, language: QueryLanguage) -> Result<Query> {
+        if self
+            .parameter_names()
+            .iter()
+            .any(|name| !self.parameters.contains_key(*name))
+        {
+            return Err(Error::cbl_error(CouchbaseLiteError::InvalidParameter));
+        }
+        let source = match language {
+            QueryLanguage::N1QL => self.to_n1ql()?,
+            QueryLanguage::JSON => self.to_json()?,
+        };
+        let query = Query::new(db, language, &source)?;
+        if !self.parameters.is_empty() {
+            let mut parameters = MutableDict::new();
+            for (name, value) in &self.parameters {
+                value.put(parameters.at(name));
+            }
+            query.set_parameters(&parameters);
+        }
+        Ok(query)
+    }
+
+    fn expressions(&self) -> impl Iterator<Item = &Expression> {
+        self.select
+            .iter()
+            .map(|result| &result.expression)
+            .chain(self.joins.iter().filter_map(|join| join.on.as_ref()))
+            .chain(self.where_.iter())
+            .chain(self.group_by.iter())
+            .chain(self.having.iter())
+            .chain(self.order_by.iter().map(|ordering| &ordering.expression))
+            .chain(self.limit.iter())
+            .chain(self.offset.iter())
+    }
+
+    fn check(&self) -> Result<&DataSource> {
+        let invalid = || Error::cbl_error(CouchbaseLiteError::InvalidQuery);
+        let from = self.from.as_ref().ok_or_else(invalid)?;
+        if self.select.is_empty() || (self.having.is_some() && self.group_by.is_empty()) {
+            return Err(invalid());
+        }
+        let mut aliases = BTreeSet::from([from.effective_alias()]);
+        for join in &self.joins {
+            if !aliases.insert(join.source.effective_alias())
+                || (join.kind == JoinKind::Cross) != join.on.is_none()
+            {
+                return Err(invalid());
+            }
+        }
+        Ok(from)
+    }
+}
+
+//////// FORMATTING UTILITIES
+
+fn check_identifier(name: &str) -> Result<()> {
+    let mut chars = name.chars();
+    let valid = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+    if !valid {
+        return Err(Error::cbl_error(CouchbaseLiteError::InvalidQuery));
+    }
+    Ok(())
+}
+
+fn format_double(value: f64) -> Result<String> {
+    if !value.is_finite() {
+        return Err(Error::cbl_error(CouchbaseLiteError::InvalidQuery));
+    }
+    Ok(format!("{value:?}"))
+}
+
+// Identifiers are always escaped with backquotes, so that reserved words and names with
+// special characters (like `-` or `%` in collection names) are safe.
+fn write_n1ql_identifier(out: &mut String, name: &str) -> Result<()> {
+    if name.is_empty() {
+        return Err(Error::cbl_error(CouchbaseLiteError::InvalidQuery));
+    }
+    out.push('`');
+    out.push_str(&name.replace('`', "``"));
+    out.push('`');
+    Ok(())
+}
+
+fn write_n1ql_list(out: &mut String, expressions: &[Expression]) -> Result<()> {
+    for (i, expression) in expressions.iter().enumerate() {
+        if i > 0 {
+            out.push_str(", ");
+        }
+        expression.write_n1ql(out, false)?;
+    }
+    Ok(())
+}
+
+fn write_json_string(out: &mut String, s: &str) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+// Property paths use `.` as separator, so special characters of the components are escaped.
+fn push_json_path_component(path: &mut String, component: &str) -> Result<()> {
+    if component.is_empty() {
+        return Err(Error::cbl_error(CouchbaseLiteError::InvalidQuery));
+    }
+    for c in component.chars() {
+        if matches!(c, '.' | '[' | '$' | '\\') {
+            path.push('\\');
+        }
+        path.push(c);
+    }
+    Ok(())
+}
+
+fn write_json_operation(out: &mut String, operator: &str, operands: &[&Expression]) -> Result<()> {
+    out.push('[');
+    write_json_string(out, operator);
+    for operand in operands {
+        out.push(',');
+        operand.write_json(out)?;
+    }
+    out.push(']');
+    Ok(())
+}
+
+fn write_json_list<'a>(
+    out: &mut String,
+    expressions: impl Iterator<Item = &'a Expression>,
+) -> Result<()> {
+    out.push('[');
+    for (i, expression) in expressions.enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        expression.write_json(out)?;
+    }
+    out.push(']');
+    Ok(())
+}
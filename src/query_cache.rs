@@ -0,0 +1,186 @@
+//! A least-recently-used cache of compiled queries, to avoid compiling the same query text
+//! each time it is run.
+
+use crate::{BindError, Database, MutableDict, Query, QueryLanguage, Result, ResultSet};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, LazyLock, Mutex, MutexGuard, Weak};
+
+// The schema generations of the databases that have caches, by path: the number of times
+// indexes were created or deleted, or collections deleted, through this crate. A change
+// invalidates the caches of the database. The caches share the counter of their database, which
+// is removed with the last of them.
+static SCHEMA_GENERATIONS: LazyLock<Mutex<HashMap<PathBuf, Weak<AtomicU64>>>> =
+    LazyLock::new(Mutex::default);
+
+fn schema_generations() -> MutexGuard<'static, HashMap<PathBuf, Weak<AtomicU64>>> {
+    SCHEMA_GENERATIONS
+        .lock()
+        .unwrap_or_else(std::sync::PoisonError::into_inner)
+}
+
+fn schema_generation(path: PathBuf) -> Arc<AtomicU64> {
+    let mut generations = schema_generations();
+    if let Some(generation) = generations.get(&path).and_then(Weak::upgrade) {
+        return generation;
+    }
+    let generation = Arc::new(AtomicU64::new(0));
+    generations.insert(path, Arc::downgrade(&generation));
+    generation
+}
+
+pub(crate) fn schema_changed(db: &Database) {
+    if let Some(generation) = schema_generations().get(&db.path()).and_then(Weak::upgrade) {
+        generation.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/** Statistics of a `QueryCache`. */
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct QueryCacheStats {
+    /** Lookups that found a compiled query. */
+    pub hits: u64,
+    /** Lookups that compiled the query. */
+    pub misses: u64,
+    /** Queries removed to make room for others. */
+    pub evictions: u64,
+    /** Times the cache was cleared, because the indexes or collections changed. */
+    pub invalidations: u64,
+}
+
+/** A cache of the compiled queries of a database, keyed by language and text, which keeps the
+most recently used queries up to its capacity.
+
+A compiled query holds its parameters, so the cached queries are only run through the cache,
+which sets their parameters for each execution.
+
+The cache is cleared when an index is created or deleted, or a collection deleted, through this
+crate; call `invalidate` after changing them by other means. */
+pub struct QueryCache {
+    db: Database,
+    path: PathBuf,
+    capacity: usize,
+    entries: HashMap<(QueryLanguage, String), (Query, u64)>,
+    clock: u64,
+    schema: Arc<AtomicU64>,
+    generation: u64,
+    stats: QueryCacheStats,
+}
+
+impl QueryCache {
+    /** Creates an empty cache for the database. A capacity of 0 disables caching. */
+    pub fn new(db: &Database, capacity: usize) -> Self {
+        let path = db.path();
+        let schema = schema_generation(path.clone());
+        Self {
+            db: db.clone(),
+            generation: schema.load(Ordering::Relaxed),
+            schema,
+            path,
+            capacity,
+            entries: HashMap::new(),
+            clock: 0,
+            stats: QueryCacheStats::default(),
+        }
+    }
+
+    /** Runs the query, compiling it if it isn't cached, without parameters. */
+    pub fn execute(&mut self, language: QueryLanguage, text: &str) -> Result<ResultSet> {
+        self.get(language, text)?.execute()
+    }
+
+    /** Runs the query, compiling it if it isn't cached, with the parameters bound like
+    `Query::bind`. */
+    pub fn execute_with<T: Serialize + ?Sized>(
+        &mut self,
+        language: QueryLanguage,
+        text: &str,
+        parameters: &T,
+    ) -> std::result::Result<ResultSet, BindError> {
+        let query = self.get(language, text)?;
+        query.bind(parameters)?;
+        Ok(query.execute()?)
+    }
+
+    // Returns the compiled query for the text, compiling it if it isn't cached, with its
+    // parameters cleared. The query is shared with the cache.
+    fn get(&mut self, language: QueryLanguage, text: &str) -> Result<Query> {
+        let generation = self.schema.load(Ordering::Relaxed);
+        if generation != self.generation {
+            self.generation = generation;
+            self.invalidate();
+        }
+
+        self.clock += 1;
+        let key = (language, text.to_string());
+        if let Some((query, used)) = self.entries.get_mut(&key) {
+            *used = self.clock;
+            self.stats.hits += 1;
+            query.set_parameters(&MutableDict::new());
+            return Ok(query.clone());
+        }
+
+        self.stats.misses += 1;
+        let query = Query::new(&self.db, language, text)?;
+        if self.capacity > 0 {
+            if self.entries.len() >= self.capacity {
+                self.evict();
+            }
+            self.entries.insert(key, (query.clone(), self.clock));
+        }
+        Ok(query)
+    }
+
+    /** Removes a query from the cache, returning true if it was cached. */
+    pub fn remove(&mut self, language: QueryLanguage, text: &str) -> bool {
+        self.entries.remove(&(language, text.to_string())).is_some()
+    }
+
+    /** Removes all the queries from the cache. */
+    pub fn invalidate(&mut self) {
+        if !self.entries.is_empty() {
+            self.entries.clear();
+            self.stats.invalidations += 1;
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub const fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    pub const fn stats(&self) -> QueryCacheStats {
+        self.stats
+    }
+
+    // Removes the least recently used query.
+    fn evict(&mut self) {
+        let oldest = self
+            .entries
+            .iter()
+            .min_by_key(|(_, (_, used))| *used)
+            .map(|(key, _)| key.clone());
+        if let Some(oldest) = oldest {
+            self.entries.remove(&oldest);
+            self.stats.evictions += 1;
+        }
+    }
+}
+
+impl Drop for QueryCache {
+    fn drop(&mut self) {
+        let mut generations = schema_generations();
+        if Arc::strong_count(&self.schema) == 1 {
+            generations.remove(&self.path);
+        }
+    }
+}
@@ -0,0 +1,224 @@
+//! Writes query results as NDJSON, CSV or a JSON array, one row at a time, so the memory used
+//! doesn't depend on the number of rows. Wrap files and sockets in a `BufWriter`.
+//! Columns without name are named `$1`, `$2`... after their position.
+
+use crate::{ResultSet, Row, Value, ValueType, fleece::FleeceReference};
+use std::io::{self, Write};
+
+/** How arrays and dictionaries are exported. */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NestedValues {
+    /** As JSON, in a JSON string in CSV. */
+    #[default]
+    Json,
+    /** Omitted: missing from JSON objects, null in JSON arrays, empty in CSV. */
+    Omit,
+}
+
+/** How blobs, the dictionaries describing attachments, are exported. */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BlobValues {
+    /** As their metadata dictionary, with the digest, length and content type. */
+    #[default]
+    Metadata,
+    /** As their digest string. */
+    Digest,
+    /** Omitted, like `NestedValues::Omit`. */
+    Omit,
+}
+
+/** Options of the query results export. */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ExportOptions {
+    pub nested: NestedValues,
+    pub blobs: BlobValues,
+}
+
+impl ResultSet {
+    /** Writes the remaining rows as newline-delimited JSON: one object per line, keyed by
+    column name. Returns the number of rows written, or the error of the writer. */
+    pub fn write_ndjson<W: Write>(self, mut out: W, options: ExportOptions) -> io::Result<u64> {
+        let columns = self.column_labels();
+        let mut count = 0;
+        for row in self {
+            write_row_json(&mut out, &row, &columns, options)?;
+            out.write_all(b"\n")?;
+            count += 1;
+        }
+        out.flush()?;
+        Ok(count)
+    }
+
+    /** Writes the remaining rows as a JSON array of objects keyed by column name, like
+    `collect_json`. Returns the number of rows written, or the error of the writer. */
+    pub fn write_json_array<W: Write>(self, mut out: W, options: ExportOptions) -> io::Result<u64> {
+        let columns = self.column_labels();
+        let mut count = 0;
+        out.write_all(b"[")?;
+        for row in self {
+            if count > 0 {
+                out.write_all(b",\n")?;
+            }
+            write_row_json(&mut out, &row, &columns, options)?;
+            count += 1;
+        }
+        out.write_all(b"]\n")?;
+        out.flush()?;
+        Ok(count)
+    }
+
+    /** Writes the remaining rows as CSV (RFC 4180), after a header with the column names.
+    Missing and null values are empty fields, strings are written as is, and nested values
+    as JSON. Returns the number of rows written, or the error of the writer. */
+    pub fn write_csv<W: Write>(self, mut out: W, options: ExportOptions) -> io::Result<u64> {
+        let columns = self.column_labels();
+        write_csv_record(&mut out, columns.iter().map(String::as_str))?;
+        let mut count = 0;
+        for row in self {
+            let fields = (0..columns.len())
+                .map(|i| csv_field(&row.get(i as isize), options))
+                .collect::<Vec<_>>();
+            write_csv_record(&mut out, fields.iter().map(String::as_str))?;
+            count += 1;
+        }
+        out.flush()?;
+        Ok(count)
+    }
+}
+
+// What a value is exported as, once the options are applied.
+enum Exported {
+    Omitted,
+    Scalar,
+    Nested,
+    Digest(String),
+}
+
+fn exported(value: &Value, options: ExportOptions) -> Exported {
+    match value.get_type() {
+        ValueType::Undefined => Exported::Omitted,
+        ValueType::Dict if value.is_blob() => match options.blobs {
+            BlobValues::Metadata => Exported::Nested,
+            BlobValues::Digest => value
+                .as_dict()
+                .get("digest")
+                .as_string()
+                .map_or(Exported::Omitted, |digest| {
+                    Exported::Digest(digest.to_string())
+                }),
+            BlobValues::Omit => Exported::Omitted,
+        },
+        ValueType::Array | ValueType::Dict => match options.nested {
+            NestedValues::Json => Exported::Nested,
+            NestedValues::Omit => Exported::Omitted,
+        },
+        _ => Exported::Scalar,
+    }
+}
+
+fn write_row_json<W: Write>(
+    out: &mut W,
+    row: &Row,
+    columns: &[String],
+    options: ExportOptions,
+) -> io::Result<()> {
+    let mut buffer = Vec::new();
+    buffer.push(b'{');
+    let mut first = true;
+    for (i, column) in columns.iter().enumerate() {
+        let value = row.get(i as isize);
+        if matches!(exported(&value, options), Exported::Omitted) {
+            continue;
+        }
+        if !first {
+            buffer.push(b',');
+        }
+        first = false;
+        write_json_string(&mut buffer, column);
+        buffer.push(b':');
+        write_json(&mut buffer, &value, options);
+    }
+    buffer.push(b'}');
+    out.write_all(&buffer)
+}
+
+// Writes a value that isn't omitted; omitted items of arrays are null.
+fn write_json(out: &mut Vec<u8>, value: &Value, options: ExportOptions) {
+    match exported(value, options) {
+        Exported::Omitted => out.extend_from_slice(b"null"),
+        Exported::Scalar => out.extend_from_slice(value.to_json().as_bytes()),
+        Exported::Digest(digest) => write_json_string(out, &digest),
+        Exported::Nested if value.get_type() == ValueType::Array => {
+            out.push(b'[');
+            for (i, item) in value.as_array().iter().enumerate() {
+                if i > 0 {
+                    out.push(b',');
+                }
+                write_json(out, &item, options);
+            }
+            out.push(b']');
+        }
+        Exported::Nested => {
+            out.push(b'{');
+            let mut first = true;
+            for (key, item) in value.as_dict().iter() {
+                if matches!(exported(&item, options), Exported::Omitted) {
+                    continue;
+                }
+                if !first {
+                    out.push(b',');
+                }
+                first = false;
+                write_json_string(out, &key);
+                out.push(b':');
+                write_json(out, &item, options);
+            }
+            out.push(b'}');
+        }
+    }
+}
+
+fn write_json_string(out: &mut Vec<u8>, string: &str) {
+    // Serializing a string can't fail.
+    let _ = serde_json::to_writer(out, string);
+}
+
+fn csv_field(value: &Value, options: ExportOptions) -> String {
+    match exported(value, options) {
+        Exported::Omitted => String::new(),
+        Exported::Digest(digest) => digest,
+        Exported::Nested => {
+            let mut json = Vec::new();
+            write_json(&mut json, value, options);
+            String::from_utf8(json).unwrap_or_default()
+        }
+        Exported::Scalar => match value.get_type() {
+            ValueType::Null => String::new(),
+            ValueType::String => value.as_string().unwrap_or_default().to_string(),
+            // Data is base64-encoded in a JSON string.
+            ValueType::Data => value.to_json().trim_matches('"').to_string(),
+            _ => value.to_json(),
+        },
+    }
+}
+
+fn write_csv_record<'a, W: Write>(
+    out: &mut W,
+    fields: impl Iterator<Item = &'a str>,
+) -> io::Result<()> {
+    let mut record = String::new();
+    for (i, field) in fields.enumerate() {
+        if i > 0 {
+            record.push(',');
+        }
+        if field.contains([',', '"', '\n', '\r']) {
+            record.push('"');
+            record.push_str(&field.replace('"', "\"\""));
+            record.push('"');
+        } else {
+            record.push_str(field);
+        }
+    }
+    record.push_str("\r\n");
+    out.write_all(record.as_bytes())
+}
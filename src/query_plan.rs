@@ -0,0 +1,219 @@
+//! Structured form of `Query::explain`: the SQLite statement a query is translated to, and the
+//! steps of the strategy SQLite picked to run it (see <https://www.sqlite.org/eqp.html>).
+
+use crate::{CouchbaseLiteError, Error, Query, Result};
+use std::str::FromStr;
+
+/** The plan of a query, parsed from `Query::explain`. */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryPlan {
+    /** The SQLite statement the query is translated to. */
+    pub sql: String,
+    /** The steps of the query strategy, in the order SQLite reports them. */
+    pub steps: Vec<PlanStep>,
+    /** The query in the JSON query schema. */
+    pub json: String,
+}
+
+impl QueryPlan {
+    /** Parses the output of `Query::explain`. */
+    pub fn parse(explain: &str) -> Result<Self> {
+        let invalid = || Error::cbl_error(CouchbaseLiteError::InvalidParameter);
+
+        // The statement, a blank line, one line per step, a blank line, and the JSON query.
+        let mut lines = explain.lines();
+        let sql: Vec<&str> = lines.by_ref().take_while(|line| !line.is_empty()).collect();
+        let steps = lines
+            .by_ref()
+            .take_while(|line| !line.is_empty())
+            .map(|line| PlanStep::parse(line).ok_or_else(invalid))
+            .collect::<Result<Vec<_>>>()?;
+        let json: Vec<&str> = lines.collect();
+
+        if sql.is_empty() || steps.is_empty() {
+            return Err(invalid());
+        }
+        Ok(Self {
+            sql: sql.join("\n"),
+            steps,
+            json: json.join("\n").trim().to_string(),
+        })
+    }
+
+    /** Returns the names of the indexes used by the query, without duplicates. */
+    pub fn indexes(&self) -> Vec<&str> {
+        let mut indexes: Vec<&str> = Vec::new();
+        for index in self.steps.iter().filter_map(|step| step.index.as_deref()) {
+            if !indexes.contains(&index) {
+                indexes.push(index);
+            }
+        }
+        indexes
+    }
+
+    /** Returns true if the query uses the named index. */
+    pub fn uses_index(&self, name: &str) -> bool {
+        self.indexes().contains(&name)
+    }
+
+    /** Returns the steps reading every row of a table. */
+    pub fn full_scans(&self) -> impl Iterator<Item = &PlanStep> {
+        self.steps.iter().filter(|step| step.is_full_scan())
+    }
+
+    /** Returns true if a step reads every row of a table. */
+    pub fn has_full_scan(&self) -> bool {
+        self.full_scans().next().is_some()
+    }
+}
+
+impl FromStr for QueryPlan {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        Self::parse(s)
+    }
+}
+
+/** What a step of a query plan does. */
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StepKind {
+    /** Reads all the rows of a table, or of an index with `index`. */
+    Scan,
+    /** Looks up a range of rows through an index or the primary key. */
+    Search,
+    /** Sorts rows in a temporary B-tree, for an `ORDER BY`, `GROUP BY` or `DISTINCT` that no
+    index provides. */
+    TempBTree,
+    /** Any other step, like a subquery or a compound query. */
+    Other,
+}
+
+/** A step of a query plan, from one line of the `EXPLAIN QUERY PLAN` output. */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlanStep {
+    pub id: i64,
+    /** The id of the enclosing step, 0 at the top level. */
+    pub parent: i64,
+    pub kind: StepKind,
+    /** The table read by a scan or search step. Recent SQLite versions only name its alias,
+    like `_doc`. */
+    pub table: Option<String>,
+    /** The alias of the table in the query, if named separately. */
+    pub alias: Option<String>,
+    /** The index used by the step, if any. */
+    pub index: Option<String>,
+    /** True if the index holds all the values used, so the table isn't read. */
+    pub covering: bool,
+    /** The step as described by SQLite, like `SEARCH _doc USING INDEX by_name (<expr>=?)`. */
+    pub detail: String,
+}
+
+impl PlanStep {
+    // Parses a line `id|parent|unused| detail`.
+    fn parse(line: &str) -> Option<Self> {
+        let mut fields = line.splitn(4, '|');
+        let id = fields.next()?.trim().parse().ok()?;
+        let parent = fields.next()?.trim().parse().ok()?;
+        fields.next()?;
+        let detail = fields.next()?.trim().to_string();
+
+        let mut step = Self {
+            id,
+            parent,
+            kind: StepKind::Other,
+            table: None,
+            alias: None,
+            index: None,
+            covering: false,
+            detail,
+        };
+        let detail = step.detail.as_str();
+        let target = if let Some(rest) = detail.strip_prefix("SCAN ") {
+            step.kind = StepKind::Scan;
+            rest
+        } else if let Some(rest) = detail.strip_prefix("SEARCH ") {
+            step.kind = StepKind::Search;
+            rest
+        } else {
+            if detail.starts_with("USE TEMP B-TREE") {
+                step.kind = StepKind::TempBTree;
+            }
+            return Some(step);
+        };
+
+        // `[TABLE ]name[ AS alias][ USING [COVERING ]INDEX index[ (constraints)]]`
+        let mut words = Words(target.strip_prefix("TABLE ").unwrap_or(target));
+        step.table = words.next().map(unquote);
+        while let Some(word) = words.next() {
+            match word {
+                "AS" => step.alias = words.next().map(unquote),
+                "COVERING" => step.covering = true,
+                "INDEX" => step.index = words.next().map(unquote),
+                _ => {}
+            }
+        }
+        Some(step)
+    }
+
+    /** Returns true if the step reads every row of a table, without any index. Scans of a
+    subquery, of a constant row or of the values of an array (like for `UNNEST`) don't count. */
+    pub fn is_full_scan(&self) -> bool {
+        self.kind == StepKind::Scan
+            && self.index.is_none()
+            && !self.detail.contains("USING")
+            && !self.detail.contains("VIRTUAL TABLE")
+            && !self.detail.contains("CONSTANT ROW")
+            && !self.detail.to_ascii_uppercase().contains("SUBQUERY")
+    }
+}
+
+// Splits on spaces, keeping double-quoted names with spaces together.
+struct Words<'a>(&'a str);
+
+impl<'a> Iterator for Words<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let rest = self.0.trim_start();
+        if rest.is_empty() {
+            return None;
+        }
+        let end = if let Some(quoted) = rest.strip_prefix('"') {
+            quoted.find('"').map_or(rest.len(), |end| end + 2)
+        } else {
+            rest.find(' ').unwrap_or(rest.len())
+        };
+        self.0 = &rest[end..];
+        Some(&rest[..end])
+    }
+}
+
+fn unquote(name: &str) -> String {
+    name.trim_matches('"').to_string()
+}
+
+/** Panics if the query doesn't use the named index, with the query plan in the message.
+Meant for tests, to catch queries that stop using an index. */
+#[track_caller]
+pub fn assert_uses_index(query: &Query, index: &str) {
+    let explain = query.explain().expect("explain query");
+    let plan = QueryPlan::parse(&explain).expect("parse query plan");
+    assert!(
+        plan.uses_index(index),
+        "query doesn't use index `{index}` (uses {:?}):\n{explain}",
+        plan.indexes()
+    );
+}
+
+/** Panics if the query reads every row of a table, with the query plan in the message.
+Meant for tests, to catch queries that stop using an index. */
+#[track_caller]
+pub fn assert_no_full_scan(query: &Query) {
+    let explain = query.explain().expect("explain query");
+    let plan = QueryPlan::parse(&explain).expect("parse query plan");
+    assert!(
+        !plan.has_full_scan(),
+        "query scans a whole table:\n{explain}"
+    );
+}
@@ -0,0 +1,214 @@
+//! Opt-in profiling of query executions: the time spent running the query and reading its
+//! rows, and the number of rows, aggregated per query text. The time the caller spends between
+//! rows isn't counted. Slow queries are logged with their plan.
+
+use crate::{
+    Database, Query, QueryLanguage, Result, ResultSet, Row,
+    logging::{self, Domain, Level},
+};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/** Aggregated statistics of the executions of a query. */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryStats {
+    pub text: String,
+    pub executions: u64,
+    /** The number of rows read, over all executions. */
+    pub rows: u64,
+    pub total_time: Duration,
+    pub max_time: Duration,
+    /** The number of executions above the slow query threshold. */
+    pub slow_executions: u64,
+}
+
+impl QueryStats {
+    fn new(text: &str) -> Self {
+        Self {
+            text: text.to_string(),
+            executions: 0,
+            rows: 0,
+            total_time: Duration::ZERO,
+            max_time: Duration::ZERO,
+            slow_executions: 0,
+        }
+    }
+
+    /** Returns the average time of an execution. */
+    pub fn mean_time(&self) -> Duration {
+        u32::try_from(self.executions)
+            .ok()
+            .filter(|executions| *executions > 0)
+            .map_or(Duration::ZERO, |executions| self.total_time / executions)
+    }
+}
+
+/** Records the executions of the queries it creates, and logs the slow ones.
+Clones of a profiler share their statistics. */
+#[derive(Clone, Default)]
+pub struct QueryProfiler {
+    shared: Arc<ProfilerShared>,
+}
+
+#[derive(Default)]
+struct ProfilerShared {
+    slow_threshold: Option<Duration>,
+    stats: Mutex<HashMap<String, QueryStats>>,
+}
+
+impl QueryProfiler {
+    /** Creates a profiler. Executions taking at least `slow_threshold` are logged as warnings
+    in the `Query` domain, with the output of `Query::explain`. */
+    pub fn new(slow_threshold: Option<Duration>) -> Self {
+        Self {
+            shared: Arc::new(ProfilerShared {
+                slow_threshold,
+                stats: Mutex::default(),
+            }),
+        }
+    }
+
+    /** Compiles a query whose executions are profiled. */
+    pub fn query(
+        &self,
+        db: &Database,
+        language: QueryLanguage,
+        text: &str,
+    ) -> Result<ProfiledQuery> {
+        Ok(self.wrap(Query::new(db, language, text)?, text))
+    }
+
+    /** Profiles the executions of a compiled query, recorded under `text`. */
+    pub fn wrap(&self, query: Query, text: &str) -> ProfiledQuery {
+        ProfiledQuery {
+            query,
+            text: text.to_string(),
+            profiler: self.clone(),
+        }
+    }
+
+    /** Returns the statistics of the queries, slowest total time first. */
+    pub fn stats(&self) -> Vec<QueryStats> {
+        let mut stats: Vec<QueryStats> = self.lock().values().cloned().collect();
+        stats.sort_by(|a, b| b.total_time.cmp(&a.total_time).then(a.text.cmp(&b.text)));
+        stats
+    }
+
+    /** Returns the statistics of a query. */
+    pub fn stats_for(&self, text: &str) -> Option<QueryStats> {
+        self.lock().get(text).cloned()
+    }
+
+    /** Forgets the recorded statistics. */
+    pub fn reset(&self) {
+        self.lock().clear();
+    }
+
+    fn lock(&self) -> MutexGuard<'_, HashMap<String, QueryStats>> {
+        self.shared
+            .stats
+            .lock()
+            .unwrap_or_else(std::sync::PoisonError::into_inner)
+    }
+
+    fn record(&self, query: &Query, text: &str, time: Duration, rows: u64) {
+        let slow = self
+            .shared
+            .slow_threshold
+            .is_some_and(|threshold| time >= threshold);
+        {
+            let mut stats = self.lock();
+            let stats = stats
+                .entry(text.to_string())
+                .or_insert_with(|| QueryStats::new(text));
+            stats.executions += 1;
+            stats.rows += rows;
+            stats.total_time += time;
+            stats.max_time = stats.max_time.max(time);
+            if slow {
+                stats.slow_executions += 1;
+            }
+        }
+        if slow {
+            let explain = query.explain().unwrap_or_default();
+            logging::write(
+                Domain::Query,
+                Level::Warning,
+                &format!("Slow query ({time:?}, {rows} rows): {text}\n{explain}"),
+            );
+        }
+    }
+}
+
+/** A query whose executions are recorded by a `QueryProfiler`. */
+pub struct ProfiledQuery {
+    query: Query,
+    text: String,
+    profiler: QueryProfiler,
+}
+
+impl ProfiledQuery {
+    /** Runs the query, like `Query::execute`. The execution is recorded when the results are
+    dropped, with the time spent in `Query::execute` and in reading the rows. */
+    pub fn execute(&self) -> Result<ProfiledResultSet> {
+        let start = Instant::now();
+        let results = self.query.execute();
+        let elapsed = start.elapsed();
+        let results = match results {
+            Ok(results) => results,
+            Err(error) => {
+                self.profiler.record(&self.query, &self.text, elapsed, 0);
+                return Err(error);
+            }
+        };
+        Ok(ProfiledResultSet {
+            results,
+            query: self.query.clone(),
+            text: self.text.clone(),
+            profiler: self.profiler.clone(),
+            elapsed,
+            rows: 0,
+        })
+    }
+
+    /** Returns the profiled query, to set its parameters for instance. */
+    pub const fn query(&self) -> &Query {
+        &self.query
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+}
+
+/** The results of a `ProfiledQuery`, counting the rows read. */
+pub struct ProfiledResultSet {
+    results: ResultSet,
+    query: Query,
+    text: String,
+    profiler: QueryProfiler,
+    elapsed: Duration,
+    rows: u64,
+}
+
+impl Iterator for ProfiledResultSet {
+    type Item = Row;
+
+    fn next(&mut self) -> Option<Row> {
+        let start = Instant::now();
+        let row = self.results.next();
+        self.elapsed += start.elapsed();
+        if row.is_some() {
+            self.rows += 1;
+        }
+        row
+    }
+}
+
+impl Drop for ProfiledResultSet {
+    fn drop(&mut self) {
+        self.profiler
+            .record(&self.query, &self.text, self.elapsed, self.rows);
+    }
+}
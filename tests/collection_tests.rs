@@ -493,3 +493,73 @@ fn queries() {
         );
     });
 }
+
+#[test]
+fn iterate_documents() {
+    utils::with_db(|db| {
+        for i in 0..25 {
+            utils::add_doc(
+                db,
+                &format!("doc-{i:02}"),
+                i,
+                if i % 2 == 0 { "even" } else { "odd" },
+            );
+        }
+        let collection = utils::default_collection(db);
+
+        let ids: Vec<String> = collection
+            .document_ids()
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(ids.len(), 25);
+        assert_eq!(ids[0], "doc-00");
+        assert_eq!(ids[24], "doc-24");
+
+        // Filtered and ordered by a property, in batches smaller than the results:
+        let options = collection::DocumentsOptions {
+            order_by: Some(query_builder::OrderBy::desc(
+                query_builder::Expression::property("i"),
+            )),
+            filter: Some(
+                query_builder::Expression::property("s")
+                    .equal_to(query_builder::Expression::value("odd")),
+            ),
+            batch_size: 5,
+        };
+        let ids: Vec<String> = collection
+            .document_ids_with(&options)
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(ids.len(), 12);
+        assert_eq!(ids[0], "doc-23");
+        assert_eq!(ids[11], "doc-01");
+
+        let documents: Vec<Document> = collection
+            .documents_with(&options)
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(documents.len(), 12);
+        assert_eq!(documents[0].id(), "doc-23");
+        assert_eq!(documents[0].properties().get("i").as_i64_or_0(), 23);
+
+        // Documents without sort key come last in descending order:
+        let mut doc = Document::new_with_id("no-i");
+        doc.mutable_properties().at("s").put_string("odd");
+        utils::default_collection(db)
+            .save_document(&mut doc)
+            .unwrap();
+        let ids: Vec<String> = collection
+            .document_ids_with(&options)
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(ids.len(), 13);
+        assert_eq!(ids[11], "doc-01");
+        assert_eq!(ids[12], "no-i");
+
+        assert_eq!(collection.documents().unwrap().count(), 26);
+    });
+}
@@ -141,6 +141,70 @@ fn database_get_document() {
     });
 }
 
+#[test]
+fn collection_get_documents() {
+    utils::with_db(|db| {
+        for (id, i) in [("a", 1), ("b", 2), ("c", 3)] {
+            let mut document = Document::new_with_id(id);
+            document
+                .set_properties_as_json(&format!(r#"{{"i":{i},"nested":{{"i":{i}}}}}"#))
+                .unwrap();
+            default_collection(db).save_document(&mut document).unwrap();
+        }
+        let ids = ["c", "missing", "a"];
+
+        let documents = default_collection(db).get_documents(&ids);
+        assert_eq!(documents.len(), 3);
+        assert_eq!(documents[0].as_ref().unwrap().as_ref().unwrap().id(), "c");
+        assert!(documents[1].as_ref().unwrap().is_none());
+        assert_eq!(documents[2].as_ref().unwrap().as_ref().unwrap().id(), "a");
+
+        let properties: Vec<_> = default_collection(db)
+            .get_documents_properties(&ids)
+            .into_iter()
+            .map(Result::unwrap)
+            .collect();
+        let json: Vec<Option<String>> = properties
+            .iter()
+            .map(|properties| properties.as_ref().map(|p| p.as_dict().to_json()))
+            .collect();
+        assert_eq!(
+            json,
+            vec![
+                Some(r#"{"i":3,"nested":{"i":3}}"#.to_string()),
+                None,
+                Some(r#"{"i":1,"nested":{"i":1}}"#.to_string()),
+            ]
+        );
+        let first = properties[0].as_ref().unwrap();
+        assert_eq!(first.get("nested").as_dict().get("i").as_i64(), Some(3));
+
+        // Deleted documents are returned:
+        let mut collection = default_collection(db);
+        let document = collection.get_document("b").unwrap();
+        collection.delete_document(&document).unwrap();
+        let documents = collection.get_documents(&["b"]);
+        assert!(
+            documents[0]
+                .as_ref()
+                .unwrap()
+                .as_ref()
+                .unwrap()
+                .is_deleted()
+        );
+        let properties = collection.get_documents_properties(&["b"]);
+        assert!(
+            properties[0]
+                .as_ref()
+                .unwrap()
+                .as_ref()
+                .unwrap()
+                .as_dict()
+                .empty()
+        );
+    });
+}
+
 #[test]
 fn database_save_document() {
     utils::with_db(|db| {
@@ -182,6 +246,169 @@ fn database_save_document() {
     });
 }
 
+#[test]
+fn collection_save_documents() {
+    utils::with_db(|db| {
+        let mut stale = Document::new_with_id("doc-1");
+        default_collection(db).save_document(&mut stale).unwrap();
+        let mut current = default_collection(db).get_document("doc-1").unwrap();
+        current.mutable_properties().at("i").put_i64(1);
+        default_collection(db).save_document(&mut current).unwrap();
+
+        stale.mutable_properties().at("i").put_i64(2);
+        let mut docs = vec![Document::new_with_id("doc-0"), stale];
+        docs.extend((2..5).map(|i| Document::new_with_id(&format!("doc-{i}"))));
+
+        let report = default_collection(db)
+            .save_documents_with_transaction_size(
+                docs.iter_mut(),
+                ConcurrencyControl::FailOnConflict,
+                2,
+            )
+            .unwrap();
+        assert_eq!(report.outcomes.len(), 5);
+        assert_eq!(report.saved(), 4);
+        assert_eq!(report.conflicts(), vec!["doc-1"]);
+        assert!(report.failures().is_empty());
+        assert!(!report.is_success());
+        assert_eq!(default_collection(db).count(), 5);
+        assert_eq!(
+            default_collection(db)
+                .get_document("doc-1")
+                .unwrap()
+                .properties()
+                .get("i")
+                .as_i64_or_0(),
+            1
+        );
+
+        // Owned documents, overwriting the conflicting revision:
+        let mut stale = docs.remove(1);
+        stale.mutable_properties().at("i").put_i64(3);
+        let report = default_collection(db)
+            .save_documents([stale], ConcurrencyControl::LastWriteWins)
+            .unwrap();
+        assert!(report.is_success());
+        assert_eq!(
+            default_collection(db)
+                .get_document("doc-1")
+                .unwrap()
+                .properties()
+                .get("i")
+                .as_i64_or_0(),
+            3
+        );
+    });
+}
+
+#[test]
+fn collection_update() {
+    utils::with_db(|db| {
+        // A missing document is created:
+        let document = default_collection(db)
+            .update("counter", |properties| {
+                properties.at("count").put_i64(1);
+                Ok(Update::Save)
+            })
+            .unwrap()
+            .unwrap();
+        assert_eq!(document.properties().get("count").as_i64_or_0(), 1);
+
+        // A concurrent change makes the first save fail, the update is retried:
+        let mut attempts = 0;
+        default_collection(db)
+            .update("counter", |properties| {
+                attempts += 1;
+                if attempts == 1 {
+                    let mut concurrent = default_collection(db).get_document("counter").unwrap();
+                    concurrent.mutable_properties().at("other").put_bool(true);
+                    default_collection(db)
+                        .save_document(&mut concurrent)
+                        .unwrap();
+                }
+                let count = properties.get("count").as_i64_or_0();
+                properties.at("count").put_i64(count + 1);
+                Ok(Update::Save)
+            })
+            .unwrap();
+        assert_eq!(attempts, 2);
+        let document = default_collection(db).get_document("counter").unwrap();
+        assert_eq!(document.properties().get("count").as_i64_or_0(), 2);
+        assert!(document.properties().get("other").as_bool_or_false());
+
+        // The attempts are bounded:
+        let options = UpdateOptions {
+            max_attempts: 3,
+            create_missing: false,
+        };
+        let mut attempts = 0;
+        let error = default_collection(db)
+            .update_with("counter", &options, |_| {
+                attempts += 1;
+                let mut concurrent = default_collection(db).get_document("counter").unwrap();
+                concurrent.mutable_properties().at("count").put_i64(0);
+                default_collection(db)
+                    .save_document(&mut concurrent)
+                    .unwrap();
+                Ok(Update::Save)
+            })
+            .unwrap_err();
+        assert_eq!(attempts, 3);
+        assert_eq!(
+            error.code,
+            ErrorCode::CouchbaseLite(CouchbaseLiteError::Conflict)
+        );
+
+        // The closure aborts the update:
+        let aborted = default_collection(db)
+            .update("counter", |properties| {
+                properties.at("count").put_i64(100);
+                Ok(Update::Abort)
+            })
+            .unwrap();
+        assert!(aborted.is_none());
+        let document = default_collection(db).get_document("counter").unwrap();
+        assert_eq!(document.properties().get("count").as_i64_or_0(), 0);
+
+        // The error of the closure is returned, and nothing saved:
+        let error = default_collection(db)
+            .update("counter", |properties| {
+                properties.at("count").put_i64(100);
+                default_collection(db).get_document("missing")?;
+                Ok(Update::Save)
+            })
+            .unwrap_err();
+        assert_eq!(
+            error.code,
+            ErrorCode::CouchbaseLite(CouchbaseLiteError::NotFound)
+        );
+        let document = default_collection(db).get_document("counter").unwrap();
+        assert_eq!(document.properties().get("count").as_i64_or_0(), 0);
+
+        // At least one attempt is needed:
+        let zero = UpdateOptions {
+            max_attempts: 0,
+            create_missing: true,
+        };
+        let error = default_collection(db)
+            .update_with("counter", &zero, |_| Ok(Update::Save))
+            .unwrap_err();
+        assert_eq!(
+            error.code,
+            ErrorCode::CouchbaseLite(CouchbaseLiteError::InvalidParameter)
+        );
+
+        // Missing documents aren't created without `create_missing`:
+        let error = default_collection(db)
+            .update_with("missing", &options, |_| Ok(Update::Save))
+            .unwrap_err();
+        assert_eq!(
+            error.code,
+            ErrorCode::CouchbaseLite(CouchbaseLiteError::NotFound)
+        );
+    });
+}
+
 #[test]
 fn database_save_document_resolving() {
     utils::with_db(|db| {
@@ -20,6 +20,8 @@
 extern crate couchbase_lite;
 
 use couchbase_lite::*;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
 use utils::{init_logging, LeakChecker};
 
 pub mod utils;
@@ -125,6 +127,145 @@ fn borrow_check() {
 }
 */
 
+#[test]
+fn timestamp_values() {
+    init_logging();
+    let _leak_checker = LeakChecker::new();
+
+    let timestamp = Timestamp::new(1218086331589);
+    let mut dict = MutableDict::new();
+    dict.at("date").put_timestamp(timestamp);
+    dict.at("millis").put_i64(timestamp.as_millis());
+    dict.at("invalid").put_string("not a date");
+
+    assert_eq!(
+        dict.get("date").as_string(),
+        Some("2008-08-07T05:18:51.589Z")
+    );
+    assert_eq!(dict.get("date").as_timestamp(), Some(timestamp));
+    assert_eq!(dict.get("millis").as_timestamp(), Some(timestamp));
+    assert_eq!(dict.get("invalid").as_timestamp(), None);
+
+    // 0 is the epoch, not a missing timestamp, and earlier times are negative:
+    dict.at("epoch").put_i64(0);
+    dict.at("before").put_i64(-1500);
+    dict.at("before_string")
+        .put_string("1969-12-31T23:59:58.500Z");
+    assert_eq!(dict.get("epoch").as_timestamp(), Some(Timestamp::new(0)));
+    assert_eq!(
+        dict.get("before").as_timestamp(),
+        Some(Timestamp::new(-1500))
+    );
+    assert_eq!(
+        dict.get("before_string").as_timestamp(),
+        Some(Timestamp::new(-1500))
+    );
+    assert_eq!(dict.get("missing").as_timestamp(), None);
+}
+
+#[test]
+fn dump_and_validate() {
+    init_logging();
+    let _leak_checker = LeakChecker::new();
+
+    let doc = Fleece::parse_json(r#"{"a":[1,2,3],"b":"hello"}"#).unwrap();
+    assert!(doc.dump().contains("\"hello\""));
+    assert!(doc.validate(Trust::Untrusted).is_ok());
+
+    let root = doc.as_dict();
+    assert!(root.dump().unwrap().contains("\"hello\""));
+    assert!(root.validate(Trust::Untrusted).is_ok());
+    assert!(Value::UNDEFINED.validate(Trust::Untrusted).is_err());
+
+    let reparsed = Fleece::parse(doc.data(), Trust::Untrusted).unwrap();
+    assert_eq!(reparsed.as_dict(), root);
+    let truncated = &doc.data()[..doc.data().len() - 1];
+    assert!(Fleece::parse(truncated, Trust::Untrusted).is_err());
+
+    // Corrupted data parsed as trusted is only detected by validation. All the bytes but the
+    // root pointer at the end are overwritten, so the root points to garbage:
+    let mut corrupted = doc.data().to_vec();
+    let end = corrupted.len() - 2;
+    corrupted[..end].fill(0xFF);
+    let corrupted = Fleece::parse(&corrupted, Trust::Trusted).unwrap();
+    assert!(corrupted.validate(Trust::Untrusted).is_err());
+    assert!(corrupted.root().validate(Trust::Untrusted).is_err());
+
+    let mut mutable = MutableDict::new();
+    mutable.at("a").put_i64(1);
+    assert!(mutable.validate(Trust::Untrusted).is_ok());
+}
+
+#[test]
+fn encoded_size() {
+    init_logging();
+    let _leak_checker = LeakChecker::new();
+
+    let doc = Fleece::parse_json(r#"{"small":1,"large":["some text","some more text"]}"#).unwrap();
+    let root = doc.as_dict();
+    let small = root.get("small").encoded_size().unwrap();
+    let large = root.get("large").encoded_size().unwrap();
+    let total = root.encoded_size().unwrap();
+    assert!(small < large);
+    assert!(large < total);
+
+    let mut dict = MutableDict::new();
+    dict.at("large").put_value(&root.get("large"));
+    assert!(dict.encoded_size().unwrap() > large);
+    assert!(Value::UNDEFINED.encoded_size().is_err());
+}
+
+#[test]
+fn json5_input() {
+    init_logging();
+    let _leak_checker = LeakChecker::new();
+
+    let doc = Fleece::parse_json5(
+        "{
+            // Human authored configuration
+            name: 'config',
+            values: [1, 2, 3,],
+        }",
+    )
+    .unwrap();
+    assert_eq!(
+        doc.as_dict().to_json(),
+        r#"{"name":"config","values":[1,2,3]}"#
+    );
+
+    assert!(Fleece::parse_json5("{name: }").is_err());
+    assert!(Fleece::parse_json("{name: 'config'}").is_err());
+}
+
+#[test]
+fn json_output_formats() {
+    init_logging();
+    let _leak_checker = LeakChecker::new();
+
+    let doc = Fleece::parse_json(r#"{"b":[1,"x]y"],"a":{},"c d":true}"#).unwrap();
+    let root = doc.as_dict();
+    assert_eq!(root.to_json_with(JsonFormat::default()), root.to_json());
+    assert_eq!(
+        root.to_json_with(JsonFormat::CANONICAL),
+        r#"{"a":{},"b":[1,"x]y"],"c d":true}"#
+    );
+    assert_eq!(
+        root.to_json_with(JsonFormat::JSON5),
+        r#"{a:{},b:[1,"x]y"],"c d":true}"#
+    );
+    assert_eq!(
+        root.to_json_with(JsonFormat {
+            canonical: true,
+            pretty: true,
+            ..Default::default()
+        }),
+        "{\n  \"a\": {},\n  \"b\": [\n    1,\n    \"x]y\"\n  ],\n  \"c d\": true\n}"
+    );
+
+    let reparsed = Fleece::parse_json5(&root.to_json_with(JsonFormat::JSON5)).unwrap();
+    assert_eq!(reparsed.as_dict(), root);
+}
+
 #[test]
 fn dict_to_hash_set() {
     init_logging();
@@ -274,3 +415,135 @@ fn array_from_iterator() {
     assert_eq!(arr.count(), 2);
     assert_eq!(arr.get(0).as_string(), Some("value1"));
 }
+
+#[test]
+fn value_hash() {
+    init_logging();
+    let _leak_checker = LeakChecker::new();
+
+    let doc = Fleece::parse_json(
+        r#"[{"a":1,"b":[true,"x"]},{"b":[true,"x"],"a":1.0},{"a":2},"s","s",null]"#,
+    )
+    .unwrap();
+    let array = doc.as_array();
+
+    let set: HashSet<Value> = array.iter().collect();
+    assert_eq!(set.len(), 4);
+    assert!(set.contains(&array.get(1)));
+
+    let dicts: HashSet<Dict> = array
+        .iter()
+        .filter(|v| v.is_type(ValueType::Dict))
+        .map(|v| v.as_dict())
+        .collect();
+    assert_eq!(dicts.len(), 2);
+
+    let mut map = HashMap::new();
+    map.insert(array.get(0), "first");
+    assert_eq!(map.get(&array.get(1)), Some(&"first"));
+}
+
+#[test]
+fn value_ordering() {
+    init_logging();
+    let _leak_checker = LeakChecker::new();
+
+    let doc = Fleece::parse_json(
+        r#"[{"b":1},[1,2],"b",2.5,true,null,{"a":1,"b":1},[1],"a",-3,false,2,{"a":1},{"a":2}]"#,
+    )
+    .unwrap();
+
+    let mut values: Vec<Value> = doc.as_array().iter().collect();
+    values.push(Value::UNDEFINED);
+    values.sort();
+    assert_eq!(values[0], Value::UNDEFINED);
+
+    let sorted: Vec<String> = values[1..].iter().map(|v| v.to_json()).collect();
+    assert_eq!(
+        sorted,
+        vec![
+            "null",
+            "false",
+            "true",
+            "-3",
+            "2",
+            "2.5",
+            r#""a""#,
+            r#""b""#,
+            "[1]",
+            "[1,2]",
+            r#"{"a":1}"#,
+            r#"{"a":2}"#,
+            r#"{"b":1}"#,
+            r#"{"a":1,"b":1}"#
+        ]
+    );
+
+    let doc = Fleece::parse_json("[1, 1.0, 1.5]").unwrap();
+    let numbers = doc.as_array();
+    assert_eq!(
+        numbers.get(0).cmp(&numbers.get(1)),
+        std::cmp::Ordering::Equal
+    );
+    assert!(numbers.get(1) < numbers.get(2));
+
+    let doc = Fleece::parse_json("[[1,2],[1,3],[1,2,0]]").unwrap();
+    let arrays = doc.as_array();
+    assert!(arrays.get(0).as_array() < arrays.get(1).as_array());
+    assert!(arrays.get(0).as_array() < arrays.get(2).as_array());
+    assert!(arrays.get(2).as_array() < arrays.get(1).as_array());
+}
+
+#[test]
+fn value_equality_matches_ordering() {
+    init_logging();
+    let _leak_checker = LeakChecker::new();
+
+    let hash = |value: &Value| {
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        value.hash(&mut hasher);
+        hasher.finish()
+    };
+
+    // Equality follows the ordering, unlike `FLValue_IsEqual` which compares numbers as doubles
+    // and NaN as different from itself.
+
+    // The largest unsigned integer and the double 2^64 differ, although they are equal
+    // as doubles, and so do 2^53 + 1 and the double 2^53:
+    let doc = Fleece::parse_json(
+        "[18446744073709551615, 1.8446744073709552e19, 9007199254740993, 9007199254740992.0]",
+    )
+    .unwrap();
+    let numbers = doc.as_array();
+    assert_ne!(numbers.get(0), numbers.get(1));
+    assert_eq!(
+        numbers.get(0).cmp(&numbers.get(1)),
+        std::cmp::Ordering::Less
+    );
+    assert_ne!(numbers.get(2), numbers.get(3));
+    assert!(numbers.get(3) < numbers.get(2));
+
+    // NaNs are equal, hash identically, and are greater than the other numbers:
+    let mut array = MutableArray::new();
+    array.append().put_f64(f64::NAN);
+    array.append().put_f64(-f64::NAN);
+    array.append().put_f64(f64::INFINITY);
+    array.append().put_i64(1);
+    let (nan, negative_nan) = (array.get(0), array.get(1));
+    assert_eq!(nan, negative_nan);
+    assert_eq!(nan.cmp(&negative_nan), std::cmp::Ordering::Equal);
+    assert_eq!(hash(&nan), hash(&negative_nan));
+    assert!(array.get(2) < negative_nan);
+    assert!(array.get(3) < negative_nan);
+    assert_eq!(nan, nan);
+
+    // Dicts of the same size with different keys, or different values, differ:
+    let doc = Fleece::parse_json(r#"[{"a":1,"b":2},{"a":1,"c":2},{"b":2,"a":1.0},{"a":1,"b":3}]"#)
+        .unwrap();
+    let dicts = doc.as_array();
+    assert_ne!(dicts.get(0), dicts.get(1));
+    assert_eq!(dicts.get(0), dicts.get(2));
+    assert_eq!(hash(&dicts.get(0)), hash(&dicts.get(2)));
+    assert_ne!(dicts.get(0), dicts.get(3));
+    assert!(dicts.get(0) < dicts.get(3));
+}
@@ -8,3 +8,53 @@ use couchbase_lite::*;
 fn couchbase_lite_c_version_test() {
     assert_eq!(couchbase_lite_c_version(), "3.2.4".to_string());
 }
+
+#[test]
+fn timestamp_iso8601() {
+    let timestamp = Timestamp::parse_iso8601("2008-08-07T05:18:51.589Z").unwrap();
+    assert_eq!(timestamp.as_millis(), 1218086331589);
+    assert_eq!(timestamp.to_iso8601(true), "2008-08-07T05:18:51.589Z");
+    assert_eq!(timestamp.to_string(), "2008-08-07T05:18:51.589Z");
+    assert_eq!(
+        "2008-08-07T05:18:51.589Z".parse::<Timestamp>().unwrap(),
+        timestamp
+    );
+
+    assert!(Timestamp::parse_iso8601("not a date").is_none());
+    assert!("not a date".parse::<Timestamp>().is_err());
+}
+
+#[test]
+fn timestamp_system_time() {
+    use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+    let timestamp = Timestamp::new(1218086331589);
+    let time = SystemTime::from(timestamp);
+    assert_eq!(time, UNIX_EPOCH + Duration::from_millis(1218086331589));
+    assert_eq!(Timestamp::from(time), timestamp);
+
+    let before_epoch = Timestamp::new(-1500);
+    let time = SystemTime::from(before_epoch);
+    assert_eq!(time, UNIX_EPOCH - Duration::from_millis(1500));
+    assert_eq!(Timestamp::from(time), before_epoch);
+
+    assert!(before_epoch < timestamp);
+
+    // Rounded down to the millisecond, before the epoch too:
+    let time = UNIX_EPOCH - Duration::from_micros(1500);
+    assert_eq!(Timestamp::from(time).as_millis(), -2);
+    let time = UNIX_EPOCH + Duration::from_micros(1500);
+    assert_eq!(Timestamp::from(time).as_millis(), 1);
+    assert_eq!(Timestamp::from(UNIX_EPOCH).as_millis(), 0);
+}
+
+#[test]
+fn timestamp_before_epoch_iso8601() {
+    let timestamp = Timestamp::parse_iso8601("1969-12-31T23:59:58.500Z").unwrap();
+    assert_eq!(timestamp.as_millis(), -1500);
+    assert_eq!(timestamp.to_iso8601(true), "1969-12-31T23:59:58.500Z");
+    assert_eq!(
+        Timestamp::new(0).to_iso8601(true),
+        "1970-01-01T00:00:00.000Z"
+    );
+}
@@ -0,0 +1,254 @@
+extern crate couchbase_lite;
+
+use couchbase_lite::index::ValueIndexConfiguration;
+use couchbase_lite::paginator::{Cursor, Paginator};
+use couchbase_lite::query_builder::{
+    DataSource, Expression, Function, Join, Meta, OrderBy, QueryBuilder, SelectResult,
+};
+
+use crate::utils::default_collection;
+
+use self::couchbase_lite::*;
+
+pub mod utils;
+
+fn people_query() -> QueryBuilder {
+    QueryBuilder::select([
+        SelectResult::from(Meta::id()),
+        SelectResult::expression(Function::upper(Expression::property("s"))).alias("name"),
+    ])
+    .from(DataSource::named("_default", "_default"))
+    .where_(
+        Expression::property("i")
+            .greater_than(Expression::parameter("min"))
+            .and(Expression::property("s").not_equal_to("it's")),
+    )
+    .order_by([OrderBy::desc(Expression::property("i"))])
+    .limit(10)
+}
+
+#[test]
+fn n1ql_output() {
+    assert_eq!(
+        people_query().to_n1ql().unwrap(),
+        "SELECT meta().id, UPPER(`s`) AS `name` FROM `_default`.`_default` \
+            WHERE (`i` > $min) AND (`s` != 'it''s') ORDER BY `i` DESC LIMIT 10"
+    );
+
+    let joined = QueryBuilder::select_distinct([Expression::property("name").of("b")])
+        .from(DataSource::named("store", "my-orders").alias("a"))
+        .join(
+            Join::left_outer(DataSource::named("store", "products").alias("b")).on(Meta::id()
+                .of("b")
+                .equal_to(Expression::property("product.id").of("a"))),
+        )
+        .group_by([Expression::property("name").of("b")])
+        .having(Function::count(Expression::all()).greater_than(1))
+        .offset(Expression::parameter("skip"));
+    assert_eq!(
+        joined.to_n1ql().unwrap(),
+        "SELECT DISTINCT `b`.`name` FROM `store`.`my-orders` AS `a` \
+            LEFT OUTER JOIN `store`.`products` AS `b` ON meta(`b`).id = `a`.`product`.`id` \
+            GROUP BY `b`.`name` HAVING COUNT(*) > 1 OFFSET $skip"
+    );
+    assert_eq!(
+        joined.parameter_names().into_iter().collect::<Vec<_>>(),
+        vec!["skip"]
+    );
+}
+
+#[test]
+fn json_output() {
+    assert_eq!(
+        people_query().to_json().unwrap(),
+        r#"{"WHAT":[["._id"],["AS",["UPPER()",[".s"]],"name"]],"#.to_string()
+            + r#""FROM":[{"SCOPE":"_default","COLLECTION":"_default"}],"#
+            + r#""WHERE":["AND",[">",[".i"],["$min"]],["!=",[".s"],"it's"]],"#
+            + r#""ORDER_BY":[["DESC",[".i"]]],"LIMIT":10}"#
+    );
+
+    let query = QueryBuilder::select([SelectResult::all().alias("doc")])
+        .from(DataSource::named("_default", "_default").alias("d"))
+        .where_(
+            Expression::property("i")
+                .between(1, 2.5)
+                .or(Expression::property("s").is_in(["a", "b"]))
+                .or(!Expression::property("x.y").is_missing()),
+        );
+    assert_eq!(
+        query.to_json().unwrap(),
+        r#"{"WHAT":[["AS",["."],"doc"]],"#.to_string()
+            + r#""FROM":[{"SCOPE":"_default","COLLECTION":"_default","AS":"d"}],"#
+            + r#""WHERE":["OR",["OR",["BETWEEN",[".i"],1,2.5],["IN",[".s"],["[]","a","b"]]],"#
+            + r#"["NOT",["IS",[".x.y"],["MISSING"]]]]}"#
+    );
+}
+
+#[test]
+fn invalid_queries() {
+    let invalid = |query: QueryBuilder| {
+        assert!(query.to_n1ql().is_err());
+        assert!(query.to_json().is_err());
+    };
+
+    // No data source, no results:
+    invalid(QueryBuilder::select([SelectResult::all()]));
+    invalid(QueryBuilder::select(Vec::<SelectResult>::new()).from(DataSource::named("a", "b")));
+    // HAVING without GROUP BY:
+    invalid(
+        QueryBuilder::select([SelectResult::all()])
+            .from(DataSource::named("a", "b"))
+            .having(Expression::value(true)),
+    );
+    // Duplicate alias, missing join condition:
+    invalid(
+        QueryBuilder::select([SelectResult::all()])
+            .from(DataSource::named("a", "b"))
+            .join(Join::inner(DataSource::named("c", "b")).on(Expression::value(true))),
+    );
+    invalid(
+        QueryBuilder::select([SelectResult::all()])
+            .from(DataSource::named("a", "b"))
+            .join(Join::inner(DataSource::named("a", "c"))),
+    );
+    // Invalid parameter name, non-finite number:
+    invalid(
+        QueryBuilder::select([Expression::parameter("not valid")])
+            .from(DataSource::named("a", "b")),
+    );
+    invalid(QueryBuilder::select([Expression::value(f64::NAN)]).from(DataSource::named("a", "b")));
+}
+
+#[test]
+fn build_and_execute() {
+    utils::with_db(|db| {
+        utils::add_doc(db, "doc-1", 1, "one");
+        utils::add_doc(db, "doc-2", 2, "two");
+        utils::add_doc(db, "doc-3", 3, "it's");
+
+        let builder = people_query().from(DataSource::collection(&default_collection(db)));
+
+        // The `min` parameter has no value:
+        assert!(builder.build(db, QueryLanguage::N1QL).is_err());
+
+        let builder = builder.set_parameter("min", 1);
+        for language in [QueryLanguage::N1QL, QueryLanguage::JSON] {
+            let query = builder.build(db, language).expect("build query");
+            let rows: Vec<_> = query
+                .execute()
+                .unwrap()
+                .map(|row| {
+                    (
+                        row.get(0).as_string().unwrap().to_string(),
+                        row.get(1).as_string().unwrap().to_string(),
+                    )
+                })
+                .collect();
+            assert_eq!(rows, vec![("doc-2".to_string(), "TWO".to_string())]);
+        }
+    });
+}
+
+#[test]
+fn keyset_pagination() {
+    utils::with_db(|db| {
+        // Two documents share the key 3, the document ID breaks the tie:
+        for (id, i) in [
+            ("a", 5),
+            ("b", 3),
+            ("c", 1),
+            ("d", 3),
+            ("e", 4),
+            ("f", 2),
+            ("g", 6),
+        ] {
+            utils::add_doc(db, id, i, id);
+        }
+        utils::add_doc(db, "filtered", 100, "filtered");
+        assert!(
+            default_collection(db)
+                .create_index(
+                    "i_index",
+                    &ValueIndexConfiguration::new(QueryLanguage::N1QL, "i", None),
+                )
+                .unwrap()
+        );
+
+        let query = QueryBuilder::select([Expression::property("s")])
+            .from(DataSource::collection(&default_collection(db)))
+            .where_(Expression::property("i").less_than(Expression::parameter("max")))
+            .set_parameter("max", 10);
+        let paginator = Paginator::new(
+            db,
+            query.clone(),
+            OrderBy::asc(Expression::property("i")),
+            3,
+        )
+        .expect("create paginator");
+
+        let strings = |rows: &[OwnedRow]| {
+            rows.iter()
+                .map(|row| row.get(0).as_string().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let first = paginator.page(None).unwrap();
+        assert_eq!(first.rows[0].column_names(), vec!["s"]);
+        assert_eq!(strings(&first.rows), vec!["c", "f", "b"]);
+
+        // The cursor can be stored as a string and resumed:
+        let cursor: Cursor = first.next.unwrap().to_string().parse().unwrap();
+        let second = paginator.page(Some(&cursor)).unwrap();
+        assert_eq!(strings(&second.rows), vec!["d", "e", "a"]);
+        let third = paginator.page(second.next.as_ref()).unwrap();
+        assert_eq!(strings(&third.rows), vec!["g"]);
+        assert!(third.next.is_none());
+
+        let descending = Paginator::new(db, query, OrderBy::desc(Expression::property("i")), 4)
+            .expect("create paginator");
+        let pages: Vec<Vec<String>> = descending
+            .pages(None)
+            .map(|page| strings(&page.unwrap().rows))
+            .collect();
+        assert_eq!(pages, vec![vec!["g", "a", "e", "d"], vec!["b", "f", "c"]]);
+
+        // Documents without key come first, or last in descending order, by ID:
+        let mut collection = default_collection(db);
+        for (id, null) in [("n2", false), ("n3", true), ("n1", false)] {
+            let mut doc = Document::new_with_id(id);
+            let mut props = doc.mutable_properties();
+            props.at("s").put_string(id);
+            if null {
+                props.at("i").put_null();
+            }
+            collection.save_document(&mut doc).unwrap();
+        }
+        let all = QueryBuilder::select([Expression::property("s")])
+            .from(DataSource::collection(&collection));
+        let pages = |key: OrderBy| -> Vec<Vec<String>> {
+            Paginator::new(db, all.clone(), key, 4)
+                .expect("create paginator")
+                .pages(None)
+                .map(|page| strings(&page.unwrap().rows))
+                .collect()
+        };
+        assert_eq!(
+            pages(OrderBy::asc(Expression::property("i"))),
+            vec![
+                vec!["n1", "n2", "n3", "c"],
+                vec!["f", "b", "d", "e"],
+                vec!["a", "g", "filtered"],
+            ]
+        );
+        assert_eq!(
+            pages(OrderBy::desc(Expression::property("i"))),
+            vec![
+                vec!["filtered", "g", "a", "e"],
+                vec!["d", "b", "f", "c"],
+                vec!["n3", "n2", "n1"],
+            ]
+        );
+
+        assert!("not a cursor".parse::<Cursor>().is_err());
+    });
+}
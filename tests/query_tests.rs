@@ -1,8 +1,20 @@
 extern crate couchbase_lite;
 extern crate regex;
 
-use couchbase_lite::index::{ValueIndexConfiguration, ArrayIndexConfiguration};
+use couchbase_lite::full_text::{Highlighter, SnippetOptions, TextOptions};
+use couchbase_lite::index::{
+    ArrayIndexConfiguration, FullTextIndexConfiguration, IndexDefinition, IndexSpec,
+    ValueIndexConfiguration,
+};
+use couchbase_lite::index_advisor::{IndexAdvisor, IndexReason, SuggestedIndex};
+use couchbase_lite::query_cache::{QueryCache, QueryCacheStats};
+use couchbase_lite::query_profiler::QueryProfiler;
+use couchbase_lite::query_export::{BlobValues, ExportOptions, NestedValues};
+use couchbase_lite::query_plan::{QueryPlan, StepKind, assert_no_full_scan, assert_uses_index};
 use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::time::Duration;
 
 use crate::utils::default_collection;
 
@@ -108,6 +120,456 @@ fn parameters() {
     });
 }
 
+#[test]
+fn bind_parameters() {
+    #[derive(Serialize)]
+    struct Filter {
+        min: i64,
+        names: Vec<&'static str>,
+    }
+
+    utils::with_db(|db| {
+        utils::add_doc(db, "doc-1", 1, "one");
+        utils::add_doc(db, "doc-2", 2, "two");
+        utils::add_doc(db, "doc-3", 3, "three");
+
+        let query = Query::new(
+            db,
+            QueryLanguage::N1QL,
+            "SELECT s FROM _ WHERE i >= $min AND ARRAY_CONTAINS($names, s) ORDER BY i",
+        )
+        .expect("create query");
+        assert_eq!(query.parameter_names().unwrap(), vec!["min", "names"]);
+
+        query
+            .bind(&Filter {
+                min: 2,
+                names: vec!["one", "two"],
+            })
+            .expect("bind struct");
+        let rows: Vec<_> = query
+            .execute()
+            .unwrap()
+            .map(|row| row.get(0).as_string().unwrap().to_string())
+            .collect();
+        assert_eq!(rows, vec!["two"]);
+
+        query
+            .bind(&params! { "min" => 1, "names" => vec!["one", "three"] })
+            .expect("bind params");
+        assert_eq!(query.execute().unwrap().count(), 2);
+
+        // Missing parameter:
+        let error = query.bind(&params! { "min" => 1 }).unwrap_err();
+        assert_eq!(
+            error,
+            BindError::MissingParameters(vec!["names".to_string()])
+        );
+        let mut partial = MutableDict::new();
+        partial.at("min").put_i64(1);
+        assert_eq!(
+            query.missing_parameters(&partial.as_dict()).unwrap(),
+            vec!["names"]
+        );
+    });
+}
+
+#[test]
+fn query_parameter_names() {
+    utils::with_db(|db| {
+        let query = Query::new(
+            db,
+            QueryLanguage::N1QL,
+            "SELECT s, '$quoted' AS `$escaped` FROM _ -- $comment
+             WHERE i > $min /* $block */ AND s != \"it''s $x\" AND s = $name_2",
+        )
+        .expect("create query");
+        assert_eq!(query.parameter_names().unwrap(), vec!["min", "name_2"]);
+        assert_eq!(
+            query.clone().parameter_names().unwrap(),
+            vec!["min", "name_2"]
+        );
+
+        let query = Query::new(
+            db,
+            QueryLanguage::JSON,
+            r#"{"WHAT": [[".s"]], "WHERE": ["AND", [">", [".i"], ["$min"]], ["=", [".s"], "$literal"]]}"#,
+        )
+        .expect("create query");
+        assert_eq!(query.parameter_names().unwrap(), vec!["min"]);
+
+        // The query passed to a change listener has no text: its parameter names come from
+        // the SQLite statement of `explain`.
+        let mut query = Query::new(db, QueryLanguage::N1QL, "SELECT s FROM _ WHERE i > $min")
+            .expect("create query");
+        query.bind(&params! { "min" => 0 }).unwrap();
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let _listener = query.add_listener(Box::new(move |query, _| {
+            let _ = sender.send(query.parameter_names());
+        }));
+        utils::add_doc(db, "doc-1", 1, "one");
+        let names = receiver
+            .recv_timeout(Duration::from_secs(10))
+            .expect("listener call");
+        assert_eq!(names.unwrap(), vec!["min"]);
+    });
+}
+
+#[test]
+fn bind_nested_parameters() {
+    utils::with_db(|db| {
+        let timestamp = Timestamp::new(1218086331589);
+        let query = Query::new(
+            db,
+            QueryLanguage::N1QL,
+            "SELECT $date AS date, $options AS options FROM _",
+        )
+        .expect("create query");
+
+        let mut options = HashMap::new();
+        options.insert("limit", 5);
+        query
+            .bind(&params! { "date" => timestamp, "options" => options })
+            .expect("bind params");
+
+        let params = query.parameters();
+        assert_eq!(params.get("date").as_timestamp(), Some(timestamp));
+        assert_eq!(
+            params.get("options").as_dict().get("limit").as_i64(),
+            Some(5)
+        );
+    });
+}
+
+#[test]
+fn typed_results() {
+    #[derive(Debug, PartialEq, Deserialize)]
+    struct Item {
+        id: String,
+        i: i64,
+        s: Option<String>,
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Label(String);
+
+    impl FromRow for Label {
+        fn from_row(row: &Row) -> Result<Self> {
+            let (id, s) = (row.get_key("id"), row.get_key("s"));
+            Ok(Label(format!(
+                "{}:{}",
+                id.as_string().unwrap_or_default(),
+                s.as_string().unwrap_or_default()
+            )))
+        }
+    }
+
+    utils::with_db(|db| {
+        utils::add_doc(db, "doc-1", 1, "one");
+        utils::add_doc(db, "doc-2", 2, "two");
+
+        let query = Query::new(
+            db,
+            QueryLanguage::N1QL,
+            "SELECT meta().id AS id, i, s FROM _ ORDER BY i",
+        )
+        .expect("create query");
+
+        let items: Vec<Item> = query
+            .execute_as::<Item>()
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(
+            items,
+            vec![
+                Item {
+                    id: "doc-1".to_string(),
+                    i: 1,
+                    s: Some("one".to_string()),
+                },
+                Item {
+                    id: "doc-2".to_string(),
+                    i: 2,
+                    s: Some("two".to_string()),
+                },
+            ]
+        );
+
+        let labels: Vec<Label> = query
+            .execute_from_row::<Label>()
+            .unwrap()
+            .collect::<Result<_>>()
+            .unwrap();
+        assert_eq!(
+            labels,
+            vec![
+                Label("doc-1:one".to_string()),
+                Label("doc-2:two".to_string())
+            ]
+        );
+
+        // A column with the wrong type:
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Wrong {
+            i: String,
+        }
+        assert!(query.execute_as::<Wrong>().unwrap().all(|r| r.is_err()));
+
+        let json: serde_json::Value =
+            serde_json::from_str(&query.execute().unwrap().collect_json()).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!([
+                {"id": "doc-1", "i": 1, "s": "one"},
+                {"id": "doc-2", "i": 2, "s": "two"},
+            ])
+        );
+    });
+}
+
+#[test]
+fn owned_rows() {
+    utils::with_db(|db| {
+        utils::add_doc(db, "doc-1", 1, "one");
+        utils::add_doc(db, "doc-2", 2, "two");
+
+        let query = Query::new(
+            db,
+            QueryLanguage::N1QL,
+            "SELECT i, s, missing FROM _ ORDER BY i",
+        )
+        .expect("create query");
+
+        let rows: Vec<OwnedRow> = query
+            .execute()
+            .unwrap()
+            .map(|row| row.to_owned().unwrap())
+            .collect();
+        assert_eq!(rows.len(), 2);
+        assert_eq!(rows[0].column_names(), vec!["i", "s", "missing"]);
+        assert_eq!(rows[0].get(0).as_i64(), Some(1));
+        assert_eq!(rows[1].get_key("s").as_string(), Some("two"));
+        assert_eq!(rows[1].get_key("missing").get_type(), ValueType::Undefined);
+        assert_eq!(rows[1].to_dict().to_json(), r#"{"i":2,"s":"two"}"#);
+
+        // Columns without name keep their position:
+        let query = Query::new(db, QueryLanguage::N1QL, "SELECT i + 1, s FROM _ ORDER BY i")
+            .expect("create query");
+        let mut results = query.execute().unwrap();
+        let row = results.next().unwrap();
+        assert_eq!(row.column_names().len(), 2);
+        assert_eq!(row.column_names()[1], Some("s"));
+        let owned = row.to_owned().unwrap();
+        assert_eq!(owned.column_names(), vec!["$1", "s"]);
+        assert_eq!(owned.get_key("$1").as_i64(), Some(2));
+        assert_eq!(owned.get_key("s").as_string(), Some("one"));
+
+        // Rows of different executions compare equal:
+        let again: Vec<OwnedRow> = query
+            .execute()
+            .unwrap()
+            .map(|row| row.to_owned().unwrap())
+            .collect();
+        assert_eq!(rows, again);
+        assert_ne!(rows[0], rows[1]);
+
+        // Rows can be sent to another thread:
+        let strings = std::thread::spawn(move || {
+            rows.iter()
+                .map(|row| row.get_key("s").as_string().unwrap().to_string())
+                .collect::<Vec<_>>()
+        })
+        .join()
+        .unwrap();
+        assert_eq!(strings, vec!["one", "two"]);
+    });
+}
+
+#[test]
+fn query_listener_called_repeatedly() {
+    utils::with_db(|db| {
+        let mut query =
+            Query::new(db, QueryLanguage::N1QL, "SELECT s FROM _").expect("create query");
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let _listener = query.add_listener(Box::new(move |query, token| {
+            let _ = sender.send(query.copy_current_results(token).map(Iterator::count));
+        }));
+        let timeout = Duration::from_secs(10);
+
+        // Each call used to drop the token it was given, removing the listener after it.
+        utils::add_doc(db, "doc-1", 1, "one");
+        let mut count = receiver.recv_timeout(timeout).expect("first call").unwrap();
+        if count == 0 {
+            count = receiver.recv_timeout(timeout).expect("call").unwrap();
+        }
+        assert_eq!(count, 1);
+        utils::add_doc(db, "doc-2", 2, "two");
+        assert_eq!(
+            receiver
+                .recv_timeout(timeout)
+                .expect("second call")
+                .unwrap(),
+            2
+        );
+    });
+}
+
+#[test]
+fn observe() {
+    utils::with_db(|db| {
+        utils::add_doc(db, "doc-1", 1, "one");
+
+        let mut query =
+            Query::new(db, QueryLanguage::N1QL, "SELECT i FROM _").expect("create query");
+        let observer = query.observe(None);
+
+        let timeout = Duration::from_secs(10);
+        let results = observer.recv_timeout(timeout).expect("initial results");
+        assert_eq!(results.unwrap().count(), 1);
+
+        utils::add_doc(db, "doc-2", 2, "two");
+        let results = observer.recv_timeout(timeout).expect("new results");
+        assert_eq!(results.unwrap().count(), 2);
+
+        // Results that weren't received are replaced by newer ones:
+        utils::add_doc(db, "doc-3", 3, "three");
+        utils::add_doc(db, "doc-4", 4, "four");
+        std::thread::sleep(Duration::from_secs(2));
+        let results = observer.try_recv().expect("latest results");
+        assert_eq!(results.unwrap().count(), 4);
+        assert!(observer.try_recv().is_none());
+
+        drop(observer);
+        utils::add_doc(db, "doc-5", 5, "five");
+    });
+}
+
+#[test]
+fn observe_debounced() {
+    utils::with_db(|db| {
+        let mut query =
+            Query::new(db, QueryLanguage::N1QL, "SELECT i FROM _").expect("create query");
+        let mut observer = query.observe(Some(Duration::from_secs(1)));
+        assert_eq!(observer.next().unwrap().unwrap().count(), 0);
+
+        // A burst of changes is delivered once:
+        for i in 0..5 {
+            utils::add_doc(db, &format!("doc-{i}"), i, "burst");
+        }
+        let results = observer
+            .recv_timeout(Duration::from_secs(10))
+            .expect("debounced results");
+        assert_eq!(results.unwrap().count(), 5);
+        assert!(observer.recv_timeout(Duration::from_secs(2)).is_none());
+    });
+}
+
+#[test]
+fn observe_diffs() {
+    utils::with_db(|db| {
+        utils::add_doc(db, "doc-1", 1, "one");
+        utils::add_doc(db, "doc-2", 2, "two");
+
+        let mut query = Query::new(
+            db,
+            QueryLanguage::N1QL,
+            "SELECT meta().id, s FROM _ ORDER BY i",
+        )
+        .expect("create query");
+        assert!(query.observe_diffs("missing", None).is_err());
+        let mut observer = query.observe_diffs("id", None).expect("observe diffs");
+
+        let ids = |rows: &[OwnedRow]| {
+            rows.iter()
+                .map(|row| row.get_key("id").as_string().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+        let timeout = Duration::from_secs(10);
+
+        let diff = observer
+            .recv_timeout(timeout)
+            .expect("initial diff")
+            .unwrap();
+        assert_eq!(ids(&diff.added), vec!["doc-1", "doc-2"]);
+        assert!(diff.removed.is_empty() && diff.changed.is_empty());
+
+        utils::add_doc(db, "doc-2", 2, "deux");
+        utils::add_doc(db, "doc-3", 3, "three");
+        let mut collection = default_collection(db);
+        let doc = collection.get_document("doc-1").unwrap();
+        collection.delete_document(&doc).unwrap();
+
+        // The changes may be delivered in several diffs:
+        let mut added = vec![];
+        let mut removed = vec![];
+        let mut changed = vec![];
+        while added.is_empty() || removed.is_empty() || changed.is_empty() {
+            let diff = observer.recv_timeout(timeout).expect("diff").unwrap();
+            added.extend(ids(&diff.added));
+            removed.extend(ids(&diff.removed));
+            changed.extend(diff.changed);
+        }
+        assert_eq!(added, vec!["doc-3"]);
+        assert_eq!(removed, vec!["doc-1"]);
+        assert_eq!(ids(&changed), vec!["doc-2"]);
+        assert_eq!(changed[0].get_key("s").as_string(), Some("deux"));
+    });
+}
+
+#[cfg(feature = "async")]
+#[test]
+fn observe_stream() {
+    use futures::{StreamExt, executor::block_on};
+
+    utils::with_db(|db| {
+        utils::add_doc(db, "doc-1", 1, "one");
+
+        let mut query =
+            Query::new(db, QueryLanguage::N1QL, "SELECT i FROM _").expect("create query");
+        let mut observer = query.observe(None);
+        let results = block_on(StreamExt::next(&mut observer)).unwrap();
+        assert_eq!(results.unwrap().count(), 1);
+
+        utils::add_doc(db, "doc-2", 2, "two");
+        let results = block_on(StreamExt::next(&mut observer)).unwrap();
+        assert_eq!(results.unwrap().count(), 2);
+    });
+}
+
+#[test]
+fn timestamp_parameters() {
+    utils::with_db(|db| {
+        let timestamp = Timestamp::new(1218086331589);
+        let mut doc = Document::new_with_id("id1");
+        doc.mutable_properties().at("date").put_timestamp(timestamp);
+        default_collection(db)
+            .save_document_with_concurency_control(&mut doc, ConcurrencyControl::FailOnConflict)
+            .expect("save");
+
+        let query = Query::new(
+            db,
+            QueryLanguage::N1QL,
+            "SELECT STR_TO_MILLIS(date) FROM _ WHERE STR_TO_MILLIS(date) < $before",
+        )
+        .expect("create query");
+
+        let mut params = MutableDict::new();
+        params
+            .at("before")
+            .put_i64(timestamp.add(Duration::from_secs(1)).as_millis());
+        query.set_parameters(&params);
+
+        let rows: Vec<_> = query
+            .execute()
+            .unwrap()
+            .map(|row| row.get(0).as_i64())
+            .collect();
+        assert_eq!(rows, vec![Some(timestamp.as_millis())]);
+    });
+}
+
 #[test]
 fn get_index() {
     utils::with_db(|db| {
@@ -153,6 +615,738 @@ fn get_index() {
     })
 }
 
+#[test]
+fn index_definition() {
+    utils::with_db(|db| {
+        let collection = db
+            .create_collection(String::from("coll"), String::from("scop"))
+            .unwrap();
+        collection
+            .create_index(
+                "by_i",
+                &ValueIndexConfiguration::new(QueryLanguage::N1QL, "i", Some("i > 0")),
+            )
+            .unwrap();
+        collection
+            .create_array_index(
+                "by_like",
+                &ArrayIndexConfiguration::new(QueryLanguage::N1QL, "likes", "").unwrap(),
+            )
+            .unwrap();
+        collection
+            .create_full_text_index(
+                "by_text",
+                &FullTextIndexConfiguration::new(QueryLanguage::N1QL, "s", true, Some("en"), None)
+                    .unwrap(),
+            )
+            .unwrap();
+
+        assert_eq!(
+            collection.get_index("by_i").unwrap().definition().unwrap(),
+            Some(IndexDefinition::Value {
+                query_language: QueryLanguage::N1QL,
+                expressions: "i".to_string(),
+                where_: Some("i > 0".to_string()),
+            })
+        );
+        assert_eq!(
+            collection
+                .get_index("by_like")
+                .unwrap()
+                .definition()
+                .unwrap(),
+            Some(IndexDefinition::Array {
+                query_language: QueryLanguage::N1QL,
+                path: "likes".to_string(),
+                expressions: String::new(),
+            })
+        );
+        assert_eq!(
+            collection
+                .get_index("by_text")
+                .unwrap()
+                .definition()
+                .unwrap(),
+            Some(IndexDefinition::FullText {
+                query_language: QueryLanguage::N1QL,
+                expressions: "s".to_string(),
+                ignore_accents: true,
+                language: Some("en".to_string()),
+                where_: None,
+            })
+        );
+
+        // The definitions are stored in the database, not in the indexed collection:
+        assert_eq!(collection.count(), 0);
+        {
+            let other = Database::open(
+                db.name(),
+                Some(DatabaseConfiguration {
+                    directory: db.path().parent().unwrap(),
+                    #[cfg(feature = "enterprise")]
+                    encryption_key: None,
+                }),
+            )
+            .unwrap();
+            let other_collection = other
+                .collection(String::from("coll"), String::from("scop"))
+                .unwrap()
+                .unwrap();
+            assert_eq!(
+                other_collection
+                    .get_index("by_text")
+                    .unwrap()
+                    .definition()
+                    .unwrap(),
+                collection
+                    .get_index("by_text")
+                    .unwrap()
+                    .definition()
+                    .unwrap()
+            );
+        }
+
+        // A re-created index has its new definition:
+        collection
+            .create_index(
+                "by_i",
+                &ValueIndexConfiguration::new(QueryLanguage::N1QL, "i, s", None),
+            )
+            .unwrap();
+        assert_eq!(
+            collection.get_index("by_i").unwrap().definition().unwrap(),
+            Some(IndexDefinition::Value {
+                query_language: QueryLanguage::N1QL,
+                expressions: "i, s".to_string(),
+                where_: None,
+            })
+        );
+
+        // The definition of a deleted index is forgotten:
+        let index = collection.get_index("by_i").unwrap();
+        assert!(collection.delete_index("by_i").unwrap());
+        assert_eq!(index.definition().unwrap(), None);
+
+        // The definitions of a deleted collection are forgotten:
+        let definitions = db
+            .collection(
+                String::from("index_definitions"),
+                String::from("couchbase_lite_rs"),
+            )
+            .unwrap()
+            .unwrap();
+        assert_eq!(definitions.count(), 1);
+        db.delete_collection(String::from("coll"), String::from("scop"))
+            .unwrap();
+        assert_eq!(definitions.count(), 0);
+    })
+}
+
+#[test]
+fn parse_query_plan() {
+    let explain = "SELECT fl_result(_doc.key) FROM kv_default AS _doc WHERE fl_value(_doc.body, 'i') > 1 ORDER BY fl_value(_doc.body, 's')\n\n\
+        3|0|0| SEARCH TABLE kv_default AS _doc USING INDEX i_index (<expr>>?)\n\
+        12|0|0| SCAN \"kv_default:unnest:likes\" AS _like\n\
+        20|0|0| USE TEMP B-TREE FOR ORDER BY\n\
+        \n\
+        {\"WHAT\":[[\"._id\"]]}\n";
+    let plan: QueryPlan = explain.parse().expect("parse plan");
+    assert!(plan.sql.starts_with("SELECT fl_result(_doc.key)"));
+    assert_eq!(plan.json, r#"{"WHAT":[["._id"]]}"#);
+    assert_eq!(plan.steps.len(), 3);
+
+    let search = &plan.steps[0];
+    assert_eq!(search.kind, StepKind::Search);
+    assert_eq!(search.table.as_deref(), Some("kv_default"));
+    assert_eq!(search.alias.as_deref(), Some("_doc"));
+    assert_eq!(search.index.as_deref(), Some("i_index"));
+    assert!(!search.covering);
+    assert_eq!(
+        plan.steps[1].table.as_deref(),
+        Some("kv_default:unnest:likes")
+    );
+    assert_eq!(plan.steps[2].kind, StepKind::TempBTree);
+
+    assert_eq!(plan.indexes(), vec!["i_index"]);
+    assert!(plan.has_full_scan());
+    assert_eq!(plan.full_scans().next().unwrap().id, 12);
+
+    assert!("not a plan".parse::<QueryPlan>().is_err());
+}
+
+#[test]
+fn query_plan() {
+    utils::with_db(|db| {
+        assert!(
+            default_collection(db)
+                .create_index(
+                    "i_index",
+                    &ValueIndexConfiguration::new(QueryLanguage::N1QL, "i", None),
+                )
+                .unwrap()
+        );
+
+        let indexed = Query::new(db, QueryLanguage::N1QL, "SELECT s FROM _ WHERE i > 1")
+            .expect("create query");
+        let plan = indexed.plan().expect("query plan");
+        assert!(plan.uses_index("i_index"));
+        assert!(!plan.has_full_scan());
+        assert_uses_index(&indexed, "i_index");
+        assert_no_full_scan(&indexed);
+
+        let scan = Query::new(db, QueryLanguage::N1QL, "SELECT s FROM _ WHERE s = 'a'")
+            .expect("create query");
+        let plan = scan.plan().expect("query plan");
+        assert!(plan.indexes().is_empty());
+        assert!(plan.has_full_scan());
+    });
+}
+
+#[test]
+fn index_advisor() {
+    utils::with_db(|db| {
+        utils::add_doc(db, "doc-1", 1, "one");
+
+        let by_s = Query::new(
+            db,
+            QueryLanguage::N1QL,
+            "SELECT i FROM _ WHERE s = $s AND i >= 0",
+        )
+        .expect("create query");
+        let likes = Query::new(
+            db,
+            QueryLanguage::N1QL,
+            "SELECT meta().id FROM _ UNNEST _.likes AS _like WHERE _like = 'travel'",
+        )
+        .expect("create query");
+        let mut advisor = IndexAdvisor::new();
+        advisor
+            .add_query("by_s", by_s.clone())
+            .add_query(
+                "by_s_and_i",
+                Query::new(
+                    db,
+                    QueryLanguage::N1QL,
+                    "SELECT s FROM _ WHERE i > 1 AND s = 'one'",
+                )
+                .expect("create query"),
+            )
+            .add_query("likes", likes.clone())
+            // Nothing to index:
+            .add_query(
+                "all",
+                Query::new(db, QueryLanguage::N1QL, "SELECT s FROM _").expect("create query"),
+            );
+
+        let suggestions = advisor.advise(db).expect("advise");
+        assert_eq!(suggestions.len(), 2);
+        assert_eq!(suggestions[0].name, "idx_s_i");
+        assert_eq!(
+            suggestions[0].index,
+            SuggestedIndex::Value {
+                expressions: vec!["s".to_string(), "i".to_string()]
+            }
+        );
+        assert_eq!(suggestions[0].reason, IndexReason::FullScan);
+        assert_eq!(suggestions[0].queries, vec!["by_s", "by_s_and_i"]);
+        assert_eq!(
+            suggestions[0].to_string(),
+            "idx_s_i ON _default._default (s, i)"
+        );
+        assert_eq!(suggestions[1].name, "idx_likes");
+        assert_eq!(
+            suggestions[1].index,
+            SuggestedIndex::Array {
+                path: "likes".to_string(),
+                expressions: vec![]
+            }
+        );
+
+        assert_eq!(
+            advisor.create_indexes(db).expect("create indexes"),
+            suggestions
+        );
+        assert_uses_index(&by_s, "idx_s_i");
+        assert_uses_index(&likes, "idx_likes");
+        assert!(advisor.advise(db).expect("advise").is_empty());
+    });
+}
+
+#[test]
+fn sync_indexes() {
+    utils::with_db(|db| {
+        let collection = default_collection(db);
+        assert!(
+            collection
+                .create_index(
+                    "legacy",
+                    &ValueIndexConfiguration::new(QueryLanguage::N1QL, "s", None),
+                )
+                .unwrap()
+        );
+
+        let specs = [
+            IndexSpec::value("by_i", QueryLanguage::N1QL, "i"),
+            IndexSpec::array("by_like", QueryLanguage::N1QL, "likes", ""),
+        ];
+        let report = collection.sync_indexes(&specs, false).expect("sync");
+        assert_eq!(report.created, vec!["by_i", "by_like"]);
+        assert!(report.updated.is_empty() && report.unchanged.is_empty());
+        assert!(report.deleted.is_empty());
+        assert_eq!(report.unknown, vec!["legacy"]);
+
+        // A changed definition is applied, unknown indexes can be deleted:
+        let specs = [
+            IndexSpec::value("by_i", QueryLanguage::N1QL, "i, s")
+                .where_("i > 0")
+                .unwrap(),
+            IndexSpec::array("by_like", QueryLanguage::N1QL, "likes", ""),
+        ];
+        let report = collection.sync_indexes(&specs, true).expect("sync");
+        assert!(report.created.is_empty() && report.unknown.is_empty());
+        assert_eq!(report.updated, vec!["by_i"]);
+        assert_eq!(report.unchanged, vec!["by_like"]);
+        assert_eq!(report.deleted, vec!["legacy"]);
+
+        // An index created with another definition outside of the declarations is updated:
+        collection
+            .create_array_index(
+                "by_like",
+                &ArrayIndexConfiguration::new(QueryLanguage::N1QL, "likes", "name").unwrap(),
+            )
+            .unwrap();
+        let report = collection.sync_indexes(&specs, true).expect("sync");
+        assert_eq!(report.unchanged, vec!["by_i"]);
+        assert_eq!(report.updated, vec!["by_like"]);
+
+        let mut names = collection.index_names().unwrap();
+        names.sort();
+        assert_eq!(names, vec!["by_i", "by_like"]);
+
+        // The query includes the condition of the partial index:
+        let query = Query::new(
+            db,
+            QueryLanguage::N1QL,
+            "SELECT s FROM _ WHERE i > 0 AND s = 'a'",
+        )
+        .expect("create query");
+        assert_uses_index(&query, "by_i");
+
+        // A full-text index can have a condition too:
+        let specs = [
+            IndexSpec::value("by_i", QueryLanguage::N1QL, "i, s")
+                .where_("i > 0")
+                .unwrap(),
+            IndexSpec::array("by_like", QueryLanguage::N1QL, "likes", ""),
+            IndexSpec::full_text("by_text", QueryLanguage::N1QL, "s", true, Some("en"))
+                .where_("i > 0")
+                .unwrap(),
+        ];
+        let report = collection.sync_indexes(&specs, true).expect("sync");
+        assert_eq!(report.created, vec!["by_text"]);
+        assert_eq!(report.unchanged, vec!["by_i", "by_like"]);
+
+        // Indexes whose definition is unknown are left untouched:
+        db.collection(
+            String::from("index_definitions"),
+            String::from("couchbase_lite_rs"),
+        )
+        .unwrap()
+        .unwrap()
+        .purge_document_by_id("collection:_default._default")
+        .unwrap();
+        let report = collection.sync_indexes(&specs, true).expect("sync");
+        assert_eq!(report.unverified, vec!["by_i", "by_like", "by_text"]);
+        assert!(report.created.is_empty() && report.updated.is_empty());
+        assert!(report.unchanged.is_empty() && report.deleted.is_empty());
+
+        let duplicate = [
+            IndexSpec::value("by_i", QueryLanguage::N1QL, "i"),
+            IndexSpec::value("by_i", QueryLanguage::N1QL, "s"),
+        ];
+        assert!(collection.sync_indexes(&duplicate, false).is_err());
+
+        let error = IndexSpec::array("by_like", QueryLanguage::N1QL, "likes", "")
+            .where_("i > 0")
+            .unwrap_err();
+        assert_eq!(
+            error.code,
+            ErrorCode::CouchbaseLite(CouchbaseLiteError::InvalidParameter)
+        );
+    });
+}
+
+#[test]
+fn query_cache() {
+    utils::with_db(|db| {
+        utils::add_doc(db, "doc-1", 1, "one");
+        utils::add_doc(db, "doc-2", 2, "two");
+
+        let mut cache = QueryCache::new(db, 2);
+        let by_i = "SELECT s FROM _ WHERE i = $i";
+        let strings = |results: ResultSet| {
+            results
+                .map(|row| row.get(0).as_string().unwrap().to_string())
+                .collect::<Vec<_>>()
+        };
+
+        let one = cache
+            .execute_with(QueryLanguage::N1QL, by_i, &params! { "i" => 1 })
+            .expect("cached query");
+        let two = cache
+            .execute_with(QueryLanguage::N1QL, by_i, &params! { "i" => 2 })
+            .expect("cached query");
+        assert_eq!(strings(one), vec!["one"]);
+        assert_eq!(strings(two), vec!["two"]);
+        assert_eq!(
+            cache.stats(),
+            QueryCacheStats {
+                hits: 1,
+                misses: 1,
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            cache
+                .execute_with(QueryLanguage::N1QL, by_i, &params! {})
+                .err(),
+            Some(BindError::MissingParameters(vec!["i".to_string()]))
+        );
+
+        // The least recently used query is evicted:
+        cache
+            .execute(QueryLanguage::N1QL, "SELECT i FROM _")
+            .unwrap();
+        cache.execute(QueryLanguage::N1QL, by_i).unwrap();
+        cache
+            .execute(QueryLanguage::N1QL, "SELECT s FROM _")
+            .unwrap();
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.stats().evictions, 1);
+        assert!(!cache.remove(QueryLanguage::N1QL, "SELECT i FROM _"));
+        assert!(cache.execute(QueryLanguage::N1QL, "not a query").is_err());
+        assert_eq!(cache.len(), 2);
+
+        // A failed index creation doesn't invalidate the cache, a successful one does:
+        let collection = default_collection(db);
+        assert!(
+            collection
+                .create_index(
+                    "bad_index",
+                    &ValueIndexConfiguration::new(QueryLanguage::N1QL, "not an expression (", None),
+                )
+                .is_err()
+        );
+        cache.execute(QueryLanguage::N1QL, by_i).unwrap();
+        assert_eq!(cache.len(), 2);
+        assert_eq!(cache.stats().invalidations, 0);
+        collection
+            .create_index(
+                "i_index",
+                &ValueIndexConfiguration::new(QueryLanguage::N1QL, "i", None),
+            )
+            .unwrap();
+        cache.execute(QueryLanguage::N1QL, by_i).unwrap();
+        assert_eq!(cache.len(), 1);
+        assert_eq!(cache.stats().invalidations, 1);
+
+        // The caches of another database aren't invalidated:
+        utils::with_db(|other| {
+            let mut other_cache = QueryCache::new(other, 2);
+            other_cache.execute(QueryLanguage::N1QL, by_i).unwrap();
+            collection.delete_index("i_index").unwrap();
+            other_cache.execute(QueryLanguage::N1QL, by_i).unwrap();
+            assert_eq!(other_cache.stats().invalidations, 0);
+        });
+        cache.execute(QueryLanguage::N1QL, by_i).unwrap();
+        assert_eq!(cache.stats().invalidations, 2);
+    });
+}
+
+#[test]
+fn query_profiler() {
+    utils::with_db(|db| {
+        utils::add_doc(db, "doc-1", 1, "one");
+        utils::add_doc(db, "doc-2", 2, "two");
+
+        // Every execution is slow with a zero threshold:
+        let profiler = QueryProfiler::new(Some(Duration::ZERO));
+        let text = "SELECT s FROM _ WHERE i >= $min";
+        let query = profiler
+            .query(db, QueryLanguage::N1QL, text)
+            .expect("create query");
+
+        query.query().bind(&params! { "min" => 1 }).unwrap();
+        assert_eq!(query.execute().unwrap().count(), 2);
+        query.query().bind(&params! { "min" => 2 }).unwrap();
+        // Only the rows read are counted:
+        let mut results = query.execute().unwrap();
+        assert!(results.next().is_some());
+        drop(results);
+
+        let other = profiler.wrap(
+            Query::new(db, QueryLanguage::N1QL, "SELECT i FROM _").unwrap(),
+            "all",
+        );
+        assert_eq!(other.execute().unwrap().count(), 2);
+
+        let stats = profiler.stats_for(text).expect("query stats");
+        assert_eq!(stats.executions, 2);
+        assert_eq!(stats.rows, 3);
+        assert_eq!(stats.slow_executions, 2);
+        assert!(stats.max_time <= stats.total_time);
+        assert!(stats.mean_time() <= stats.max_time);
+        assert_eq!(profiler.stats().len(), 2);
+
+        profiler.reset();
+        assert!(profiler.stats().is_empty());
+
+        // The time spent by the caller between rows isn't counted:
+        let profiler = QueryProfiler::new(Some(Duration::from_secs(1)));
+        let query = profiler
+            .query(db, QueryLanguage::N1QL, "SELECT i FROM _")
+            .expect("create query");
+        for _ in query.execute().unwrap() {
+            std::thread::sleep(Duration::from_millis(600));
+        }
+        let stats = profiler.stats_for("SELECT i FROM _").expect("query stats");
+        assert_eq!(stats.rows, 2);
+        assert_eq!(stats.slow_executions, 0);
+        assert!(stats.total_time < Duration::from_secs(1));
+    });
+}
+
+#[test]
+fn export_results() {
+    utils::with_db(|db| {
+        let mut doc = Document::new_with_id("doc-1");
+        let mut props = doc.mutable_properties();
+        props.at("name").put_string("Smith, \"Jo\"");
+        props
+            .at("tags")
+            .put_value(&Fleece::parse_json(r#"["a","b"]"#).unwrap().as_array());
+        props
+            .at("file")
+            .put_blob(&mut Blob::new_from_data(b"hello", "text/plain"));
+        default_collection(db)
+            .save_document_with_concurency_control(&mut doc, ConcurrencyControl::FailOnConflict)
+            .expect("save");
+        utils::add_doc(db, "doc-2", 2, "two");
+
+        let query = Query::new(
+            db,
+            QueryLanguage::N1QL,
+            "SELECT meta().id, name, tags, file FROM _ ORDER BY meta().id",
+        )
+        .expect("create query");
+        let export = |write: fn(ResultSet, &mut Vec<u8>, ExportOptions) -> std::io::Result<u64>,
+                      options: ExportOptions| {
+            let mut out = Vec::new();
+            assert_eq!(
+                write(query.execute().unwrap(), &mut out, options).unwrap(),
+                2
+            );
+            String::from_utf8(out).unwrap()
+        };
+        let digest_options = ExportOptions {
+            nested: NestedValues::Json,
+            blobs: BlobValues::Digest,
+        };
+        let digest = default_collection(db)
+            .get_document("doc-1")
+            .unwrap()
+            .properties()
+            .get("file")
+            .as_dict()
+            .get("digest")
+            .as_string()
+            .unwrap()
+            .to_string();
+
+        let ndjson = export(|r, out, o| r.write_ndjson(out, o), digest_options);
+        let lines: Vec<serde_json::Value> = ndjson
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(
+            lines,
+            vec![
+                serde_json::json!({
+                    "id": "doc-1", "name": "Smith, \"Jo\"", "tags": ["a", "b"], "file": digest
+                }),
+                serde_json::json!({ "id": "doc-2" }),
+            ]
+        );
+
+        let omit = ExportOptions {
+            nested: NestedValues::Omit,
+            blobs: BlobValues::Omit,
+        };
+        let array = export(|r, out, o| r.write_json_array(out, o), omit);
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&array).unwrap(),
+            serde_json::json!([{ "id": "doc-1", "name": "Smith, \"Jo\"" }, { "id": "doc-2" }])
+        );
+
+        let csv = export(|r, out, o| r.write_csv(out, o), digest_options);
+        assert_eq!(
+            csv,
+            format!(
+                "id,name,tags,file\r\n\
+                 doc-1,\"Smith, \"\"Jo\"\"\",\"[\"\"a\"\",\"\"b\"\"]\",{digest}\r\n\
+                 doc-2,,,\r\n"
+            )
+        );
+
+        // Blob metadata is exported as a dictionary:
+        let metadata = export(|r, out, o| r.write_ndjson(out, o), ExportOptions::default());
+        let first: serde_json::Value =
+            serde_json::from_str(metadata.lines().next().unwrap()).unwrap();
+        assert_eq!(first["file"]["digest"], serde_json::json!(digest));
+        assert_eq!(first["file"]["@type"], serde_json::json!("blob"));
+
+        assert_eq!(
+            query.execute().unwrap().column_names(),
+            vec![
+                Some("id".to_string()),
+                Some("name".to_string()),
+                Some("tags".to_string()),
+                Some("file".to_string())
+            ]
+        );
+
+        // The error of the writer is returned as is:
+        struct Full;
+        impl std::io::Write for Full {
+            fn write(&mut self, _: &[u8]) -> std::io::Result<usize> {
+                Err(std::io::Error::new(
+                    std::io::ErrorKind::StorageFull,
+                    "disk full",
+                ))
+            }
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+        let error = query
+            .execute()
+            .unwrap()
+            .write_csv(Full, ExportOptions::default())
+            .unwrap_err();
+        assert_eq!(error.kind(), std::io::ErrorKind::StorageFull);
+        assert_eq!(error.to_string(), "disk full");
+    });
+}
+
+#[cfg(feature = "arrow")]
+#[test]
+fn arrow_record_batches() {
+    use arrow_array::{Array, Float64Array, Int64Array, StringArray};
+    use arrow_schema::{DataType, Field, Schema};
+    use couchbase_lite::query_arrow::ArrowOptions;
+    use std::sync::Arc;
+
+    utils::with_db(|db| {
+        for i in 0..5 {
+            utils::add_doc(db, &format!("doc-{i}"), i, &format!("s{i}"));
+        }
+        let query = Query::new(
+            db,
+            QueryLanguage::N1QL,
+            "SELECT i, s, i / 2.0 AS half, nothing FROM _ ORDER BY i",
+        )
+        .expect("create query");
+
+        // Inferred schema, over several batches:
+        let options = ArrowOptions {
+            batch_size: 2,
+            infer_rows: 3,
+            schema: None,
+        };
+        let batches = query
+            .execute()
+            .unwrap()
+            .to_record_batches(&options)
+            .unwrap();
+        assert_eq!(
+            batches.iter().map(|b| b.num_rows()).collect::<Vec<_>>(),
+            vec![2, 2, 1]
+        );
+        let schema = batches[0].schema();
+        let types: Vec<_> = schema
+            .fields()
+            .iter()
+            .map(|f| f.data_type().clone())
+            .collect();
+        assert_eq!(
+            types,
+            vec![
+                DataType::Int64,
+                DataType::Utf8,
+                DataType::Float64,
+                DataType::Utf8
+            ]
+        );
+        let last = &batches[2];
+        let i = last
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap();
+        assert_eq!(i.value(0), 4);
+        let s = last
+            .column(1)
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .unwrap();
+        assert_eq!(s.value(0), "s4");
+        let half = last
+            .column(2)
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .unwrap();
+        assert_eq!(half.value(0), 2.0);
+        assert!(last.column(3).is_null(0));
+
+        // Explicit schema, matched by column name:
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("s", DataType::LargeUtf8, true),
+            Field::new("i", DataType::Float64, true),
+            Field::new("other", DataType::Int32, true),
+        ]));
+        let options = ArrowOptions {
+            schema: Some(schema.clone()),
+            ..Default::default()
+        };
+        let batches = query
+            .execute()
+            .unwrap()
+            .to_record_batches(&options)
+            .unwrap();
+        assert_eq!(batches.len(), 1);
+        assert_eq!(batches[0].schema(), schema);
+        assert_eq!(batches[0].num_rows(), 5);
+        assert_eq!(batches[0].column(2).null_count(), 5);
+
+        // A value that doesn't fit its column:
+        let options = ArrowOptions {
+            schema: Some(Arc::new(Schema::new(vec![Field::new(
+                "s",
+                DataType::Int64,
+                true,
+            )]))),
+            ..Default::default()
+        };
+        assert!(
+            query
+                .execute()
+                .unwrap()
+                .to_record_batches(&options)
+                .is_err()
+        );
+    });
+}
+
 fn get_index_name_from_explain(explain: &str) -> Option<String> {
     Regex::new(r"USING INDEX (\w+) ")
         .unwrap()
@@ -427,3 +1621,185 @@ fn array_index() {
         assert!(result.next().is_none());
     })
 }
+
+#[test]
+fn full_text_highlighting() {
+    utils::with_db(|db| {
+        let mut collection = default_collection(db);
+        let mut doc = Document::new_with_id("trip");
+        doc.set_properties_as_json(
+            r#"{"text":"We went skiing in the Alps. Skis were rented, and the café served hot chocolate."}"#,
+        )
+        .unwrap();
+        collection.save_document(&mut doc).unwrap();
+
+        let config =
+            FullTextIndexConfiguration::new(QueryLanguage::N1QL, "text", true, Some("en"), None)
+                .unwrap();
+        assert!(
+            collection
+                .create_full_text_index("text_index", &config)
+                .unwrap()
+        );
+
+        let search = "skis AND cafe";
+        let query = Query::new(
+            db,
+            QueryLanguage::N1QL,
+            "SELECT text FROM _ WHERE MATCH(text_index, $search)",
+        )
+        .unwrap();
+        query.bind(&params! { "search" => search }).unwrap();
+        let mut results = query.execute().unwrap();
+        let row = results.next().unwrap();
+        assert!(results.next().is_none());
+
+        let options = TextOptions {
+            language: Some("en".to_string()),
+            ignore_accents: true,
+        };
+        let highlighter = Highlighter::for_match(search, &options).unwrap();
+        assert_eq!(
+            highlighter.row_matches(&row, "text").unwrap(),
+            vec![8..14, 28..32, 54..59]
+        );
+        let snippet = SnippetOptions {
+            max_words: 6,
+            ..SnippetOptions::default()
+        };
+        assert_eq!(
+            highlighter.row_snippet(&row, "text", &snippet).unwrap(),
+            "We went <b>skiing</b> in the Alps…"
+        );
+        assert_eq!(highlighter.row_snippet(&row, "nothing", &snippet), None);
+    })
+}
+
+#[test]
+fn highlighter() {
+    let text = "Running dogs ran past the running Dog, then Café Über.";
+    let options = SnippetOptions::default();
+
+    // Without language and accent folding, words only differ by case:
+    let plain = Highlighter::for_terms(&["dog", "cafe", "run*"], &TextOptions::default()).unwrap();
+    assert_eq!(
+        plain.highlight(text, &options),
+        "<b>Running</b> dogs ran past the <b>running</b> <b>Dog</b>, then Café Über."
+    );
+
+    let english = TextOptions {
+        language: Some("english".to_string()),
+        ignore_accents: true,
+    };
+    let stemmed = Highlighter::for_terms(&["dog", "cafe", "runs"], &english).unwrap();
+    assert_eq!(
+        stemmed.highlight(text, &options),
+        "<b>Running</b> <b>dogs</b> ran past the <b>running</b> <b>Dog</b>, then <b>Café</b> Über."
+    );
+    let derived = Highlighter::for_terms(&["connect"], &english).unwrap();
+    assert_eq!(
+        derived.highlight("Connections connected", &options),
+        "<b>Connections</b> <b>connected</b>"
+    );
+
+    // Other languages have their own stemmer:
+    let french = TextOptions {
+        language: Some("fr".to_string()),
+        ignore_accents: false,
+    };
+    assert_eq!(
+        Highlighter::for_terms(&["chevaux"], &french)
+            .unwrap()
+            .highlight("un cheval", &options),
+        "un <b>cheval</b>"
+    );
+    let unsupported = TextOptions {
+        language: Some("klingon".to_string()),
+        ignore_accents: false,
+    };
+    assert_eq!(
+        Highlighter::for_terms(&["dog"], &unsupported)
+            .unwrap_err()
+            .code,
+        ErrorCode::CouchbaseLite(CouchbaseLiteError::InvalidParameter)
+    );
+
+    // Operators, column filters, phrases and excluded terms of MATCH expressions:
+    let expression = Highlighter::for_match(
+        r#"text:"past the" OR (uber NOT (dog OR ran)) NEAR/2 then*"#,
+        &english,
+    )
+    .unwrap();
+    assert_eq!(
+        expression.highlight(text, &options),
+        "Running dogs ran <b>past</b> <b>the</b> running Dog, <b>then</b> Café <b>Über</b>."
+    );
+    assert!(
+        Highlighter::for_match("NOT AND", &english)
+            .unwrap()
+            .is_empty()
+    );
+
+    // Snippets show the words around most matches:
+    let snippet = SnippetOptions {
+        max_words: 4,
+        start_mark: "[".to_string(),
+        end_mark: "]".to_string(),
+        ellipsis: "...".to_string(),
+    };
+    assert_eq!(
+        stemmed.snippet(text, &snippet),
+        "...[running] [Dog], then [Café]..."
+    );
+    assert_eq!(
+        Highlighter::for_terms(&["cat"], &english)
+            .unwrap()
+            .snippet(text, &snippet),
+        "Running dogs ran past..."
+    );
+}
+
+#[test]
+fn validate_query() {
+    utils::with_db(|db| {
+        assert!(validate(db, "SELECT i, s FROM _ WHERE i > $min").is_ok());
+
+        let diagnostic = validate(db, "SELECT i,\n  s FROM _ WHER i > 1").unwrap_err();
+        assert_eq!(
+            diagnostic.error.code,
+            ErrorCode::CouchbaseLite(CouchbaseLiteError::InvalidQuery)
+        );
+        assert_eq!(diagnostic.line, 2);
+        let offset = diagnostic.offset.unwrap();
+        assert_eq!(diagnostic.column, offset - "SELECT i,\n".len() + 1);
+        assert!(diagnostic.excerpt.starts_with("  s FROM _ WHER i > 1\n"));
+        assert!(diagnostic.excerpt.ends_with('^'));
+        assert!(!diagnostic.message.is_empty());
+
+        // The position of the error is in characters, not bytes:
+        let ascii = validate(db, "SELECT 'hello world' AS t,\n  s FROM _ WHER i > 1").unwrap_err();
+        let accented =
+            validate(db, "SELECT 'héllo wörld' AS t,\n  s FROM _ WHER i > 1").unwrap_err();
+        assert_eq!(accented.line, 2);
+        assert_eq!((accented.line, accented.column), (ascii.line, ascii.column));
+        assert_eq!(accented.offset.unwrap(), ascii.offset.unwrap() + 2);
+        assert_eq!(accented.excerpt, ascii.excerpt);
+    });
+
+    let validator = QueryValidator::new()
+        .unwrap()
+        .with_collection("travel", "hotels")
+        .unwrap();
+    validator.assert_valid(&[
+        (
+            "HOTELS",
+            "SELECT name FROM travel.hotels WHERE city = $city",
+        ),
+        ("COUNT", "SELECT COUNT(*) FROM _"),
+    ]);
+    assert!(
+        validator
+            .validate("SELECT name FROM travel.motels")
+            .is_err()
+    );
+}